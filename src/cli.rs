@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use zellij_command_hook::kdl::{CommandForm, Eol, Indent};
+use zellij_command_hook::scan::{ConflictPolicy, MaxFilesAction, OutputFormat};
 
 #[derive(Parser)]
 #[command(name = "zellij-command-hook")]
@@ -10,22 +13,315 @@ pub struct Cli {
     /// Verbose output
     #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    /// Config file [default: ~/.config/zellij-command-hook/config.kdl]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Log file, created along with its parent directories if missing [default: /tmp/nvim-resurrect.log]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Hook mode: only print the formatted command, without logging it
+    #[arg(long)]
+    pub print_command_only: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Scan and simplify session layout files
-    ScanLayouts {
-        /// Path to scan
+    ScanLayouts(Box<ScanArgs>),
+    /// Answer newline-delimited JSON requests on stdin until EOF, for plugin integration
+    Serve,
+    /// Rewrite a log file written by older versions as JSON lines
+    MigrateLog {
+        /// Log file to migrate [default: the --log-file path]
+        path: Option<PathBuf>,
+    },
+    /// Remove the backups and sidecar reports written next to layouts
+    Clean {
+        /// Path to clean
         #[arg(default_value = "~/.cache/zellij")]
         path: String,
 
-        /// Dry run - don't make changes, just show what would change
+        /// Only list the files that would be removed
         #[arg(short, long)]
         dry_run: bool,
-
-        /// Quiet - don't print anything
-        #[arg(short, long)]
-        quiet: bool,
     },
+    /// Print how a KDL layout parses, as an indented tree of nodes, entries and children
+    DumpAst {
+        /// Layout file to parse
+        file: PathBuf,
+    },
+    /// Check that simplifying a layout gives the expected content, printing a diff if not
+    Verify {
+        /// Layout file to simplify
+        file: PathBuf,
+        /// File with the expected simplified layout
+        expected: PathBuf,
+    },
+    /// Show which editor a command runs and how it would be simplified, without touching files
+    Detect {
+        /// Command line to inspect
+        command: String,
+        /// Also simplify vim, emacs or another recognized editor with its built-in rule, not just nvim
+        #[arg(long)]
+        simplify_other_editors: bool,
+        /// Match editor names case-insensitively (always on for Windows builds)
+        #[arg(long)]
+        ignore_case: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct ScanArgs {
+    /// Path to scan
+    #[arg(default_value = "~/.cache/zellij")]
+    pub path: String,
+
+    /// Process the layout of this zellij session instead of scanning PATH; repeat for several
+    #[arg(long = "session", value_name = "NAME", conflicts_with = "path")]
+    pub sessions: Vec<String>,
+
+    /// Dry run - don't make changes, just show what would change
+    #[arg(short, long)]
+    pub dry_run: bool,
+
+    /// Quiet - don't print anything
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Only print the paths of modified files, one per line
+    #[arg(long)]
+    pub only_changed: bool,
+
+    /// Audit the layouts inside a zip archive instead of scanning PATH, without extracting it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["path", "sessions", "stdin", "patch", "interactive"])]
+    pub from_zip: Option<PathBuf>,
+
+    /// Write the simplified layouts back into the --from-zip archive (stored uncompressed)
+    #[arg(long, requires = "from_zip")]
+    pub rewrite_zip: bool,
+
+    /// Read a layout from stdin and write the simplified layout to stdout (with --dry-run, a diff)
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Show each file's diff and ask before applying it
+    #[arg(long, conflicts_with_all = ["dry_run", "patch", "stdin"])]
+    pub interactive: bool,
+
+    /// Overwrite files even if they changed on disk during the run
+    #[arg(long)]
+    pub force: bool,
+
+    /// Don't take the zch.lock file next to the log file that keeps two runs from overlapping
+    #[arg(long)]
+    pub no_lock: bool,
+
+    /// Process layouts that aren't valid UTF-8, replacing invalid bytes
+    #[arg(long)]
+    pub lossy: bool,
+
+    /// Write rewritten layouts without checking that they still parse as KDL
+    #[arg(long)]
+    pub no_validate: bool,
+
+    /// Detect editors behind wrapper scripts by reading the script's shebang
+    #[arg(long)]
+    pub resolve_shebang: bool,
+
+    /// Replace file args with absolute paths, resolved against the pane's cwd
+    #[arg(long)]
+    pub canonicalize_paths: bool,
+
+    /// Remove start_suspended from the panes that get simplified
+    #[arg(long)]
+    pub clear_suspended: bool,
+
+    /// Keep a pane's full editor path unless the bare editor name is found on PATH
+    #[arg(long)]
+    pub verify_editor_exists: bool,
+
+    /// What to write as a simplified pane's command
+    #[arg(long, value_enum, default_value_t = CommandForm::Bare)]
+    pub command_form: CommandForm,
+
+    /// Editor to recognize panes for; repeat for several [default: nvim, vim, emacs]
+    #[arg(long = "editor", value_name = "NAME")]
+    pub editors: Vec<String>,
+
+    /// Also simplify panes running vim, emacs or another --editor with its built-in rule, not just nvim
+    #[arg(long)]
+    pub simplify_other_editors: bool,
+
+    /// Program that runs the editor after its own args, like mise in `mise exec -- nvim`; repeat for several
+    #[arg(long = "launcher", value_name = "NAME")]
+    pub launchers: Vec<String>,
+
+    /// Only process and report panes running this editor; repeat for several
+    #[arg(long = "only-editor", value_name = "NAME")]
+    pub only_editors: Vec<String>,
+
+    /// Match editor names case-insensitively (always on for Windows builds)
+    #[arg(long)]
+    pub ignore_case: bool,
+
+    /// Write the changes as a unified diff to this file instead of editing layouts in place
+    #[arg(long, value_name = "FILE")]
+    pub patch: Option<PathBuf>,
+
+    /// Write rewritten layouts under this directory, mirroring their paths from PATH, instead of editing them in place
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["patch", "interactive", "from_zip"])]
+    pub output_dir: Option<PathBuf>,
+
+    /// What to do when a file already exists in --output-dir
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Overwrite)]
+    pub on_conflict: ConflictPolicy,
+
+    /// Only treat trailing nvim args with one of these extensions as files, e.g. rs,txt,md
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    pub file_extensions: Vec<String>,
+
+    /// Wait at least this many milliseconds between layout writes, to spread out I/O
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub write_delay: u64,
+
+    /// Skip a layout, with a warning, if rewriting it takes longer than this many milliseconds
+    #[arg(long, value_name = "MS")]
+    pub file_timeout: Option<u64>,
+
+    /// Read N layouts before rewriting any of them, to amortize latency on network storage
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub read_batch: usize,
+
+    /// Write a <FILE>.zch-report.json next to each rewritten layout, listing its changed commands
+    #[arg(long)]
+    pub sidecar_report: bool,
+
+    /// Keep N generations of backups of rewritten layouts, as <FILE>.bak, <FILE>.bak.1, ...; 0 disables them
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub keep_backups: usize,
+
+    /// Name of the pane child node holding the command's arguments
+    #[arg(long, value_name = "NAME", default_value = "args")]
+    pub args_node: String,
+
+    /// Match the args node name case-insensitively, e.g. treat Args as args
+    #[arg(long)]
+    pub args_node_ignore_case: bool,
+
+    /// Rewrite inline args="..." properties as args child nodes
+    #[arg(long)]
+    pub inline_args_to_child: bool,
+
+    /// Indentation for newly created lines: a number of spaces, or "tab" [default: match the file]
+    #[arg(long, value_name = "N")]
+    pub indent: Option<Indent>,
+
+    /// Line endings of rewritten layouts
+    #[arg(long, value_enum, default_value_t = Eol::Preserve)]
+    pub eol: Eol,
+
+    /// Rewrite simplified nvim panes that open one file and carry no other attribute or child as pane edit="file"
+    #[arg(long)]
+    pub convert_to_edit_when_single: bool,
+
+    /// Simplify nvim commands by piping each command line to PROGRAM and using what it prints
+    #[arg(long, value_name = "PROGRAM")]
+    pub transform_cmd: Option<PathBuf>,
+
+    /// Write the children of simplified panes on the pane's line, e.g. pane command="nvim" { args "a.txt"; }
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Only report nvim panes in the same tab that open the same files, without changing anything
+    #[arg(long, conflicts_with_all = ["stdin", "from_zip", "interactive", "patch", "output_dir"])]
+    pub report_redundant_panes: bool,
+
+    /// Only report panes that mention a known editor but aren't simplified, without changing anything
+    #[arg(long, conflicts_with_all = ["stdin", "from_zip", "interactive", "patch", "output_dir", "report_redundant_panes"])]
+    pub report_unrecognized: bool,
+
+    /// Only report layouts without any pane, such as stubs of dead sessions, without changing anything
+    #[arg(long, conflicts_with_all = ["stdin", "from_zip", "interactive", "patch", "output_dir", "report_redundant_panes", "report_unrecognized"])]
+    pub report_empty_sessions: bool,
+
+    /// Summarize changes per session directory instead of per file
+    #[arg(long)]
+    pub group_by_session: bool,
+
+    /// Summarize changes as a tree of sessions, files and panes instead of per file
+    #[arg(long, conflicts_with = "group_by_session")]
+    pub tree: bool,
+
+    /// After the summary, list the N changes with the longest original commands
+    #[arg(long, value_name = "N")]
+    pub report_largest: Option<usize>,
+
+    /// With --verbose, only detail the first N changes and count the rest
+    #[arg(long, value_name = "N")]
+    pub preview_count: Option<usize>,
+
+    /// After the summary, count the extensions of the files opened by the simplified commands
+    #[arg(long)]
+    pub report_by_extension: bool,
+
+    /// Also write the changes and stats as JSON to this file
+    #[arg(long, value_name = "PATH")]
+    pub summary_json_file: Option<PathBuf>,
+
+    /// Only process layouts directly in PATH, not in its subdirectories
+    #[arg(long)]
+    pub no_recurse: bool,
+
+    /// Process any *.kdl file with a top-level layout or pane node, not just session-layout.kdl files
+    #[arg(long)]
+    pub by_content: bool,
+
+    /// Stop discovery once more than N layout files are found
+    #[arg(long, value_name = "N")]
+    pub max_files: Option<usize>,
+
+    /// What to do when --max-files is exceeded
+    #[arg(long, value_enum, default_value_t = MaxFilesAction::Error)]
+    pub on_max_files: MaxFilesAction,
+
+    /// Print the time spent discovering, reading, transforming and writing layouts to stderr
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Show an editor under another name in the report, as EDITOR=NAME; repeat for several
+    #[arg(long = "editor-alias", value_name = "EDITOR=NAME", value_parser = parse_alias)]
+    pub editor_aliases: Vec<(String, String)>,
+
+    /// Format of the report printed at the end of the run; json also writes warnings and errors to stderr as JSON lines
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+
+    /// With --dry-run, exit with 1 if any layout would be changed
+    #[arg(long, requires = "dry_run")]
+    pub detect: bool,
+
+    /// Exit code scheme
+    #[arg(long, value_enum, default_value_t = ExitCodes::Default)]
+    pub exit_codes: ExitCodes,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExitCodes {
+    /// 0 once the scan ran, even if files were changed or some couldn't be processed; 1 if it couldn't run
+    Default,
+    /// 0 if no changes were needed, 10 if changes were applied, 1 on errors, including per-file ones
+    Extended,
+}
+
+/// Parses an `--editor-alias` value such as `nvim=Neovim`.
+fn parse_alias(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((editor, name)) if !editor.is_empty() && !name.is_empty() => {
+            Ok((editor.to_string(), name.to_string()))
+        }
+        _ => Err(format!("expected EDITOR=NAME, got {:?}", value)),
+    }
 }
@@ -0,0 +1,233 @@
+//! User configuration, read from `$XDG_CONFIG_HOME/zellij-command-hook/config.kdl`.
+//!
+//! ```kdl
+//! editors "nvim" "vim"
+//! indent 2
+//! max_command_len 1048576
+//! editor_aliases nvim="Neovim"
+//! ```
+
+use crate::error::ScanError;
+use crate::kdl::Indent;
+use crate::utils::expand_home;
+use kdl::KdlDocument;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variable seeding the recognized editors, e.g. `ZCH_EDITORS=nvim,vim`.
+pub const EDITORS_ENV: &str = "ZCH_EDITORS";
+
+/// Settings from the config file. Unset keys fall back to the built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Editor basenames whose panes are simplified.
+    pub editors: Option<Vec<String>>,
+    /// Indentation for newly created lines, a number of spaces or `"tab"`.
+    pub indent: Option<Indent>,
+    /// Commands longer than this many bytes are left unchanged by the hook.
+    pub max_command_len: Option<usize>,
+    /// Names to show editors under in reports, keyed by editor basename.
+    pub editor_aliases: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// The default config file location.
+    pub fn default_path() -> PathBuf {
+        let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(expand_home("~/.config")),
+        };
+        config_dir.join("zellij-command-hook").join("config.kdl")
+    }
+
+    /// Loads the config at `path`, or the default location if `None`. A missing default
+    /// config is not an error; a missing explicitly given one is.
+    pub fn load(path: Option<&Path>) -> Result<Config, ScanError> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (Config::default_path(), false),
+        };
+        if !explicit && !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = std::fs::read_to_string(&path).map_err(|source| ScanError::Read {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Config::parse(&path.display().to_string(), &content)
+    }
+
+    /// Parses config file content. `path` is only used in error messages.
+    pub fn parse(path: &str, content: &str) -> Result<Config, ScanError> {
+        let parse_error = |message: String| ScanError::Parse {
+            path: path.to_string(),
+            message,
+        };
+        let doc: KdlDocument = content.parse().map_err(|e| parse_error(format!("{}", e)))?;
+
+        let mut config = Config::default();
+        for node in doc.nodes() {
+            match node.name().value() {
+                "editors" => config.editors = Some(string_args(node.entries())),
+                "indent" => {
+                    let value = node.entries().first().map(|e| e.value());
+                    let text = value
+                        .and_then(|v| {
+                            v.as_string()
+                                .map(|s| s.to_string())
+                                .or_else(|| v.as_i64().map(|n| n.to_string()))
+                        })
+                        .unwrap_or_default();
+                    config.indent = Some(text.parse().map_err(parse_error)?);
+                }
+                "max_command_len" => {
+                    let value = node.entries().first().and_then(|e| e.value().as_i64());
+                    let len = value.and_then(|n| usize::try_from(n).ok()).ok_or_else(|| {
+                        parse_error("max_command_len must be a non-negative integer".to_string())
+                    })?;
+                    config.max_command_len = Some(len);
+                }
+                "editor_aliases" => {
+                    for entry in node.entries() {
+                        let alias = entry.name().zip(entry.value().as_string());
+                        let Some((editor, alias)) = alias else {
+                            return Err(parse_error(
+                                "editor_aliases takes editor=\"Name\" properties".to_string(),
+                            ));
+                        };
+                        config
+                            .editor_aliases
+                            .insert(editor.value().to_string(), alias.to_string());
+                    }
+                }
+                other => return Err(parse_error(format!("unknown config key {:?}", other))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Positional string values of a config node.
+fn string_args(entries: &[kdl::KdlEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|e| e.name().is_none())
+        .filter_map(|e| e.value().as_string().map(|s| s.to_string()))
+        .collect()
+}
+
+/// Splits a colon- or comma-separated editor list, as used by `ZCH_EDITORS`.
+pub fn parse_editor_list(list: &str) -> Vec<String> {
+    list.split([':', ','])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Picks the recognized editors: `--editor` flags, then the config file, then the
+/// `ZCH_EDITORS` environment variable, then `default`.
+pub fn resolve_editors(
+    flags: &[String],
+    config: &Config,
+    env: Option<&str>,
+    default: Vec<String>,
+) -> Vec<String> {
+    if !flags.is_empty() {
+        return flags.to_vec();
+    }
+    if let Some(editors) = &config.editors {
+        return editors.clone();
+    }
+    match env.map(parse_editor_list) {
+        Some(editors) if !editors.is_empty() => editors,
+        _ => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("config.kdl", "editors \"nvim\" \"vim\"\n").unwrap();
+        assert_eq!(config.editors, Some(names(&["nvim", "vim"])));
+
+        assert_eq!(Config::parse("config.kdl", "").unwrap(), Config::default());
+        assert_eq!(
+            Config::parse("config.kdl", "indent 2\n").unwrap().indent,
+            Some(Indent::Spaces(2))
+        );
+        assert_eq!(
+            Config::parse("config.kdl", "indent \"tab\"\n")
+                .unwrap()
+                .indent,
+            Some(Indent::Tab)
+        );
+        assert_eq!(
+            Config::parse("config.kdl", "max_command_len 4096\n")
+                .unwrap()
+                .max_command_len,
+            Some(4096)
+        );
+        assert!(matches!(
+            Config::parse("config.kdl", "max_command_len -1\n"),
+            Err(ScanError::Parse { .. })
+        ));
+        assert_eq!(
+            Config::parse("config.kdl", "editor_aliases nvim=\"Neovim\" vim=\"Vim\"\n")
+                .unwrap()
+                .editor_aliases,
+            BTreeMap::from([
+                ("nvim".to_string(), "Neovim".to_string()),
+                ("vim".to_string(), "Vim".to_string())
+            ])
+        );
+        assert!(matches!(
+            Config::parse("config.kdl", "editor_aliases \"Neovim\"\n"),
+            Err(ScanError::Parse { .. })
+        ));
+        assert!(matches!(
+            Config::parse("config.kdl", "editor \"nvim\"\n"),
+            Err(ScanError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_editors_precedence() {
+        let default = names(&["nvim", "vim", "emacs"]);
+        let config = Config {
+            editors: Some(names(&["emacs"])),
+            ..Default::default()
+        };
+        let flags = names(&["helix"]);
+
+        assert_eq!(
+            resolve_editors(&flags, &config, Some("vim"), default.clone()),
+            flags
+        );
+        assert_eq!(
+            resolve_editors(&[], &config, Some("vim"), default.clone()),
+            names(&["emacs"])
+        );
+        assert_eq!(
+            resolve_editors(&[], &Config::default(), Some("nvim:vim"), default.clone()),
+            names(&["nvim", "vim"])
+        );
+        assert_eq!(
+            resolve_editors(&[], &Config::default(), Some(""), default.clone()),
+            default
+        );
+        assert_eq!(
+            resolve_editors(&[], &Config::default(), None, default.clone()),
+            default
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Warnings and errors written to stderr, either as text or, for `--format json`, as one JSON
+//! object per line so both output streams stay machine-readable.
+
+use crate::error::ScanError;
+use crate::json::Json;
+
+/// How warnings and errors are written to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Diagnostics {
+    /// `Warning: ...` and `Error: ...` lines.
+    #[default]
+    Text,
+    /// `{"level":"warn","message":"...","file":"..."}` lines.
+    Json,
+}
+
+/// Severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warn,
+    Error,
+}
+
+impl Diagnostics {
+    /// Writes a warning, optionally about `file`, to stderr.
+    pub fn warn(self, message: &str, file: Option<&str>) {
+        eprintln!("{}", self.render(Level::Warn, message, file));
+    }
+
+    /// Writes an error, optionally about `file`, to stderr.
+    pub fn error(self, message: &str, file: Option<&str>) {
+        eprintln!("{}", self.render(Level::Error, message, file));
+    }
+
+    /// Writes a per-file scan error to stderr. As text this keeps the `Error reading ...` form.
+    pub fn scan_error(self, error: &ScanError) {
+        match self {
+            Diagnostics::Text => eprintln!("Error {}", error),
+            Diagnostics::Json => self.error(&error.to_string(), error.path()),
+        }
+    }
+
+    /// Formats one diagnostic line, without the trailing newline.
+    pub fn render(self, level: Level, message: &str, file: Option<&str>) -> String {
+        match self {
+            Diagnostics::Text => {
+                let label = match level {
+                    Level::Warn => "Warning",
+                    Level::Error => "Error",
+                };
+                format!("{}: {}", label, message)
+            }
+            Diagnostics::Json => {
+                let level = match level {
+                    Level::Warn => "warn",
+                    Level::Error => "error",
+                };
+                let mut entries = vec![
+                    ("level", Json::from(level)),
+                    ("message", Json::from(message)),
+                ];
+                if let Some(file) = file {
+                    entries.push(("file", Json::from(file)));
+                }
+                Json::object(entries).to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            Diagnostics::Text.render(Level::Warn, "nvim is not on PATH", Some("a.kdl")),
+            "Warning: nvim is not on PATH"
+        );
+
+        let line = Diagnostics::Json.render(Level::Warn, "say \"hi\"", Some("/tmp/a.kdl"));
+        let parsed = json::parse(&line).unwrap();
+        assert_eq!(parsed.get("level").and_then(Json::as_str), Some("warn"));
+        assert_eq!(
+            parsed.get("message").and_then(Json::as_str),
+            Some("say \"hi\"")
+        );
+        assert_eq!(
+            parsed.get("file").and_then(Json::as_str),
+            Some("/tmp/a.kdl")
+        );
+
+        let line = Diagnostics::Json.render(Level::Error, "boom", None);
+        assert_eq!(line, json::parse(&line).unwrap().to_string());
+        assert!(json::parse(&line).unwrap().get("file").is_none());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::nvim::{
+    EXECUTABLE_EXTENSIONS, VALUE_FLAGS, could_be_filename, nvim_file_args, program_stem,
+};
+use std::fmt;
+
+/// Describes how to simplify commands for one editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorRule {
+    /// Executable basename the rule applies to, e.g. "nvim".
+    pub name: String,
+    /// Flags that take no value and are kept in the simplified command.
+    pub bool_flags: Vec<String>,
+    /// Flags that take a value; both are kept in the simplified command.
+    pub keep_value_flags: Vec<String>,
+    /// Flags that take a value; both are dropped from the simplified command.
+    pub drop_value_flags: Vec<String>,
+}
+
+impl EditorRule {
+    /// A rule for `name` that drops every flag and keeps only the files.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bool_flags: Vec::new(),
+            keep_value_flags: Vec::new(),
+            drop_value_flags: Vec::new(),
+        }
+    }
+
+    /// Checks if a command token (possibly a full path) names this rule's editor. With
+    /// `ignore_case`, `VIM.EXE` and `Emacs` match too.
+    pub fn matches(&self, token: &str, ignore_case: bool) -> bool {
+        let stem = program_stem(token, EXECUTABLE_EXTENSIONS, ignore_case);
+        if ignore_case {
+            stem.eq_ignore_ascii_case(&self.name)
+        } else {
+            stem == self.name
+        }
+    }
+}
+
+fn list(flags: &[&str]) -> Vec<String> {
+    flags.iter().map(|f| f.to_string()).collect()
+}
+
+/// Built-in rules for nvim, vim and emacs.
+pub fn default_rules() -> Vec<EditorRule> {
+    let vim_bool_flags = [
+        "-d",
+        "-R",
+        "-M",
+        "-b",
+        "-o",
+        "-O",
+        "-p",
+        "-n",
+        "--clean",
+        "--noplugin",
+    ];
+    vec![
+        EditorRule {
+            bool_flags: list(&vim_bool_flags),
+            drop_value_flags: list(VALUE_FLAGS),
+            ..EditorRule::new("nvim")
+        },
+        EditorRule {
+            bool_flags: list(&vim_bool_flags),
+            drop_value_flags: list(VALUE_FLAGS),
+            ..EditorRule::new("vim")
+        },
+        EditorRule {
+            bool_flags: list(&[
+                "-nw",
+                "--no-window-system",
+                "-q",
+                "--no-init-file",
+                "-Q",
+                "--quick",
+                "--debug-init",
+            ]),
+            keep_value_flags: list(&["-l", "--load"]),
+            drop_value_flags: list(&["--eval", "--init-directory", "--chdir"]),
+            ..EditorRule::new("emacs")
+        },
+    ]
+}
+
+/// Names of the editors with built-in rules.
+pub fn default_editors() -> Vec<String> {
+    default_rules().into_iter().map(|r| r.name).collect()
+}
+
+/// Rules for the given editors: the built-in rule where there is one, otherwise a rule
+/// that drops every flag.
+pub fn rules_for(editors: &[String]) -> Vec<EditorRule> {
+    let builtin = default_rules();
+    editors
+        .iter()
+        .map(|name| {
+            builtin
+                .iter()
+                .find(|r| &r.name == name)
+                .cloned()
+                .unwrap_or_else(|| EditorRule::new(name))
+        })
+        .collect()
+}
+
+/// Simplifies an editor's argv using the first rule whose editor matches the program.
+/// Known flags are kept or dropped per the rule, unknown flags are dropped, and the
+/// trailing filenames are kept. The argv of editors without a rule is returned unchanged.
+pub fn format_with_rules(argv: &[String], rules: &[EditorRule], ignore_case: bool) -> Vec<String> {
+    let Some((first, args)) = argv.split_first() else {
+        return Vec::new();
+    };
+    let Some(rule) = rules.iter().find(|r| r.matches(first, ignore_case)) else {
+        return argv.to_vec();
+    };
+
+    let (kept_flags, file_names) = split_args(rule, args.iter().map(String::as_str));
+    std::iter::once(rule.name.as_str())
+        .chain(kept_flags)
+        .chain(file_names)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Splits an editor's args into the flags `rule` keeps and the trailing filenames.
+fn split_args<'a>(
+    rule: &EditorRule,
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let mut kept_flags: Vec<&str> = Vec::new();
+    let mut file_names: Vec<&str> = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        if token.starts_with('-') && token.len() > 1 {
+            // Filenames only count if they come after every flag
+            file_names.clear();
+
+            let (flag, inline_value) = match token.split_once('=') {
+                Some((flag, _)) if token.starts_with("--") => (flag, true),
+                _ => (token, false),
+            };
+
+            if rule.keep_value_flags.iter().any(|f| f == flag) {
+                kept_flags.push(token);
+                if !inline_value && let Some(value) = tokens.next() {
+                    kept_flags.push(value);
+                }
+            } else if rule.drop_value_flags.iter().any(|f| f == flag) {
+                if !inline_value {
+                    tokens.next();
+                }
+            } else if rule.bool_flags.iter().any(|f| f == flag) {
+                kept_flags.push(token);
+            }
+        } else if could_be_filename(token) {
+            file_names.push(token);
+        } else {
+            file_names.clear();
+        }
+    }
+
+    (kept_flags, file_names)
+}
+
+/// What the tool makes of a command line, as reported by the `detect` subcommand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    /// The recognized editor, if any.
+    pub editor: Option<String>,
+    /// Whether simplifying would change the command.
+    pub would_simplify: bool,
+    /// Files the simplified command would open.
+    pub files: Vec<String>,
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "editor: {}, would_simplify: {}, files: [{}]",
+            self.editor.as_deref().unwrap_or("none"),
+            self.would_simplify,
+            self.files.join(", ")
+        )
+    }
+}
+
+/// Detects which of `editors` a command line runs and what simplifying it would do. Editors
+/// other than nvim are only simplified with `simplify_other_editors`, like in scan-layouts.
+pub fn detect(
+    command: &str,
+    editors: &[String],
+    ignore_case: bool,
+    simplify_other_editors: bool,
+) -> Detection {
+    let tokens: Vec<&str> = command.split(' ').collect();
+    if editors.iter().any(|e| e == "nvim")
+        && let Some(files) = nvim_file_args(&tokens, ignore_case)
+    {
+        return Detection {
+            editor: Some("nvim".to_string()),
+            would_simplify: std::iter::once("nvim")
+                .chain(files.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ")
+                != command,
+            files: files.iter().map(|f| f.to_string()).collect(),
+        };
+    }
+
+    let rules = rules_for(editors);
+    let argv: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+    let rule = argv
+        .first()
+        .and_then(|first| rules.iter().find(|r| r.matches(first, ignore_case)));
+    match rule {
+        Some(rule) => {
+            let (_, files) = split_args(rule, argv[1..].iter().map(String::as_str));
+            Detection {
+                editor: Some(rule.name.clone()),
+                would_simplify: simplify_other_editors
+                    && format_with_rules(&argv, &rules, ignore_case) != argv,
+                files: files.iter().map(|f| f.to_string()).collect(),
+            }
+        }
+        None => Detection {
+            editor: None,
+            would_simplify: false,
+            files: Vec::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(command: &str) -> Vec<String> {
+        command.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_format_with_rules_emacs() {
+        let rules = default_rules();
+        let cases = [
+            (
+                "/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-emacs-29.4/bin/emacs -nw --init-directory /nix/store/4pm6h00i8jizd1vcfh90gkfsipd634rc-emacs-config --eval (server-start) notes.org src/main.rs",
+                "emacs -nw notes.org src/main.rs",
+            ),
+            (
+                "emacs -nw -l init.el todo.org",
+                "emacs -nw -l init.el todo.org",
+            ),
+            ("emacs --eval=(server-start) todo.org", "emacs todo.org"),
+        ];
+
+        for (input, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                format_with_rules(&argv(input), &rules, false).join(" "),
+                *expected,
+                "Failed on input: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_with_rules_nvim() {
+        let rules = default_rules();
+        let cases = [
+            (
+                "/home/zach/.nix-profile/bin/nvim --cmd lua vim.opt.packpath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.g.loaded_node_provider=0 -d asdf3 asdf4",
+                "nvim -d asdf3 asdf4",
+            ),
+            ("nvim -u /nix/store/init.lua file.txt", "nvim file.txt"),
+            ("vim -R file.txt", "vim -R file.txt"),
+        ];
+
+        for (input, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                format_with_rules(&argv(input), &rules, false).join(" "),
+                *expected,
+                "Failed on input: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_with_rules_unknown_editor() {
+        let rules = default_rules();
+        assert_eq!(
+            format_with_rules(&argv("helix --config x file.txt"), &rules, false),
+            argv("helix --config x file.txt")
+        );
+        assert!(format_with_rules(&[], &rules, false).is_empty());
+    }
+
+    #[test]
+    fn test_rule_matches_ignore_case() {
+        let rules = default_rules();
+        let rule = |name: &str| rules.iter().find(|r| r.name == name).unwrap();
+        let cases = [
+            ("vim", "/usr/bin/vim", false, true),
+            ("vim", "VIM.EXE", false, false),
+            ("vim", "VIM.EXE", true, true),
+            ("emacs", "/Applications/Emacs", false, false),
+            ("emacs", "/Applications/Emacs", true, true),
+            ("emacs", "emacsclient", true, false),
+        ];
+
+        for (name, token, ignore_case, expected) in cases {
+            dbg!(token);
+            assert_eq!(
+                rule(name).matches(token, ignore_case),
+                expected,
+                "Failed on input: {token} (ignore_case: {ignore_case})"
+            );
+        }
+        assert_eq!(
+            format_with_rules(&argv("VIM.EXE -R a.txt"), &rules, true),
+            argv("vim -R a.txt")
+        );
+    }
+
+    #[test]
+    fn test_format_with_rules_keeps_argv_tokens() {
+        let rules = default_rules();
+        let cases: [(&[&str], &[&str]); 3] = [
+            (
+                &["/usr/bin/vim", "-c", "set nu", "My Notes.md"],
+                &["vim", "My Notes.md"],
+            ),
+            (
+                &["vim", "-S", "Session.vim", "-t", "main", "a.txt"],
+                &["vim", "a.txt"],
+            ),
+            (
+                &[
+                    "nvim",
+                    "--startuptime",
+                    "log.txt",
+                    "-w",
+                    "keys",
+                    "-R",
+                    "b c.txt",
+                ],
+                &["nvim", "-R", "b c.txt"],
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let input: Vec<String> = input.iter().map(|s| s.to_string()).collect();
+            assert_eq!(
+                format_with_rules(&input, &rules, false),
+                expected,
+                "Failed on input: {input:?}"
+            );
+        }
+    }
+    #[test]
+    fn test_detect() {
+        let editors = default_editors();
+        let detection = detect(
+            "/home/zach/.nix-profile/bin/nvim --cmd lua vim.opt.packpath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.g.loaded_node_provider=0 a.txt src/main.rs",
+            &editors,
+            false,
+            false,
+        );
+        assert_eq!(
+            detection.to_string(),
+            "editor: nvim, would_simplify: true, files: [a.txt, src/main.rs]"
+        );
+
+        assert_eq!(
+            detect("emacs -nw -l init.el todo.org", &editors, false, false).to_string(),
+            "editor: emacs, would_simplify: false, files: [todo.org]"
+        );
+        assert_eq!(
+            detect("nvim", &editors, false, false).to_string(),
+            "editor: nvim, would_simplify: false, files: []"
+        );
+        assert_eq!(
+            detect("nvim a.txt", &editors, false, false).to_string(),
+            "editor: nvim, would_simplify: false, files: [a.txt]"
+        );
+        assert_eq!(
+            detect("vim -u vimrc a.txt", &editors, false, false).to_string(),
+            "editor: vim, would_simplify: false, files: [a.txt]"
+        );
+        assert_eq!(
+            detect("vim -u vimrc a.txt", &editors, false, true).to_string(),
+            "editor: vim, would_simplify: true, files: [a.txt]"
+        );
+        assert_eq!(
+            detect("VIM -u vimrc a.txt", &editors, true, true).to_string(),
+            "editor: vim, would_simplify: true, files: [a.txt]"
+        );
+        assert_eq!(
+            detect("bacon clippy", &editors, false, false).to_string(),
+            "editor: none, would_simplify: false, files: []"
+        );
+    }
+}
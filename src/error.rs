@@ -0,0 +1,110 @@
+use std::fmt;
+
+/// Errors that can occur while scanning and rewriting layout files.
+#[derive(Debug)]
+pub enum ScanError {
+    /// The scan root is not a directory.
+    NotADirectory { path: String },
+    /// A layout file (or stdin) could not be read.
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    /// A rewritten layout file could not be written back.
+    Write {
+        path: String,
+        source: std::io::Error,
+    },
+    /// A generated file could not be removed by `clean`.
+    Remove {
+        path: String,
+        source: std::io::Error,
+    },
+    /// A layout file is not valid UTF-8.
+    Encoding { path: String, valid_up_to: usize },
+    /// A layout file is not valid KDL.
+    Parse { path: String, message: String },
+    /// A rewrite produced content that doesn't parse as KDL, so it wasn't written.
+    InvalidOutput { path: String, message: String },
+    /// No zellij version directory has a layout for the named session.
+    SessionNotFound { name: String, cache_dir: String },
+    /// The scan found more layout files than `--max-files` allows.
+    TooManyFiles { path: String, limit: usize },
+    /// A file to be written to `--output-dir` already exists, with `--on-conflict error`.
+    OutputExists { path: String },
+    /// `--interactive` was given but stdin isn't a terminal to prompt on.
+    NotATerminal,
+}
+
+impl ScanError {
+    /// The file or directory the error is about, if there is one.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            ScanError::NotADirectory { path }
+            | ScanError::Read { path, .. }
+            | ScanError::Write { path, .. }
+            | ScanError::Remove { path, .. }
+            | ScanError::Encoding { path, .. }
+            | ScanError::Parse { path, .. }
+            | ScanError::InvalidOutput { path, .. }
+            | ScanError::TooManyFiles { path, .. }
+            | ScanError::OutputExists { path } => Some(path),
+            ScanError::SessionNotFound { .. } | ScanError::NotATerminal => None,
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::NotADirectory { path } => write!(f, "{} is not a directory", path),
+            ScanError::Read { path, source } => write!(f, "reading {}: {}", path, source),
+            ScanError::Write { path, source } => write!(f, "writing to {}: {}", path, source),
+            ScanError::Remove { path, source } => write!(f, "removing {}: {}", path, source),
+            ScanError::Encoding { path, valid_up_to } => write!(
+                f,
+                "reading {}: not valid UTF-8 after byte {} (use --lossy to process anyway)",
+                path, valid_up_to
+            ),
+            ScanError::Parse { path, message } => write!(f, "parsing {}: {}", path, message),
+            ScanError::InvalidOutput { path, message } => {
+                write!(f, "{} would have produced invalid KDL: {}", path, message)
+            }
+            ScanError::SessionNotFound { name, cache_dir } => {
+                write!(f, "no layout for session {} under {}", name, cache_dir)
+            }
+            ScanError::TooManyFiles { path, limit } => write!(
+                f,
+                "more than {} layout files under {} (raise --max-files)",
+                limit, path
+            ),
+            ScanError::OutputExists { path } => write!(
+                f,
+                "{} already exists (use --on-conflict to overwrite or skip it)",
+                path
+            ),
+            ScanError::NotATerminal => write!(
+                f,
+                "--interactive needs a terminal on stdin; drop --interactive to apply changes without prompting"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Read { source, .. }
+            | ScanError::Write { source, .. }
+            | ScanError::Remove { source, .. } => Some(source),
+            ScanError::NotADirectory { .. }
+            | ScanError::Encoding { .. }
+            | ScanError::Parse { .. }
+            | ScanError::InvalidOutput { .. }
+            | ScanError::SessionNotFound { .. }
+            | ScanError::TooManyFiles { .. }
+            | ScanError::OutputExists { .. }
+            | ScanError::NotATerminal => None,
+        }
+    }
+}
@@ -1,198 +1,384 @@
-use crate::nvim::format_nvim;
-use chrono::Local;
-use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
-use std::io::Write;
-use std::path::Path;
+use crate::diagnostic::Diagnostics;
+use crate::editor::{EditorRule, default_editors, format_with_rules, rules_for};
+use crate::json::Json;
+use crate::nvim::{could_be_filename, has_extension, is_nvim, nvim_file_args_matching};
+use crate::utils::find_in_path;
+use kdl::{KdlDocument, KdlEntry, KdlError, KdlNode, KdlValue};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct Changes {
     pub file_path: String,
     pub original_command: String,
     pub simplified_command: String,
+    /// Basename of the editor the pane runs, if it was recognized as one.
+    pub editor: Option<String>,
+    /// Files the simplified command opens, as extracted while simplifying it.
+    pub files: Vec<String>,
+    /// Position of the pane in its layout among all pane nodes, in document order, from 1.
+    pub pane: usize,
 }
 
-/// Scans a directory recursively for session-layout.kdl files and simplifies nvim commands.
-pub fn scan_layouts(dir_path: &str, verbose: bool, dry_run: bool, quiet: bool) {
-    let path = Path::new(dir_path);
-    if !path.is_dir() {
-        eprintln!("Error: {} is not a directory", dir_path);
-        return;
-    }
-
-    if dry_run {
-        println!("===============DRY RUN===============");
+impl Changes {
+    /// Bytes the simplification removes from the command (0 if it grew, e.g. direnv wrapping).
+    pub fn bytes_saved(&self) -> usize {
+        self.original_command
+            .len()
+            .saturating_sub(self.simplified_command.len())
     }
 
-    if !quiet {
-        println!("Scanning {} for session-layout.kdl files...", dir_path);
+    pub(crate) fn to_json(&self) -> Json {
+        Json::object([
+            ("file_path", Json::from(self.file_path.as_str())),
+            (
+                "original_command",
+                Json::from(self.original_command.as_str()),
+            ),
+            (
+                "simplified_command",
+                Json::from(self.simplified_command.as_str()),
+            ),
+            (
+                "editor",
+                self.editor.as_deref().map_or(Json::Null, Json::from),
+            ),
+        ])
     }
+}
 
-    let mut changes = Vec::new();
-    scan_dir_recursive(path, &mut changes, verbose, dry_run);
+/// Options controlling how pane commands are rewritten.
+#[derive(Debug, Clone)]
+pub struct TransformOptions {
+    /// Detect editors behind wrapper scripts by reading the script's shebang line.
+    pub resolve_shebang: bool,
+    /// Name of the child node holding a pane's positional arguments.
+    pub args_node: String,
+    /// Match the args child node's name case-insensitively, so `Args` counts as `args`.
+    pub args_node_ignore_case: bool,
+    /// Match editor names case-insensitively (always on for Windows builds).
+    pub ignore_case: bool,
+    /// Basenames of the editors whose panes are recognized.
+    pub editors: Vec<String>,
+    /// Also simplify panes running vim, emacs or another of `editors` with its [`EditorRule`].
+    /// nvim panes are always simplified.
+    pub simplify_other_editors: bool,
+    /// Rewrite inline `args="..."` properties as args child nodes instead of keeping them inline.
+    pub inline_args_to_child: bool,
+    /// Indentation for newly created lines. `None` matches the surrounding nodes.
+    pub indent: Option<Indent>,
+    /// Only touch panes running one of these editors. Empty means every pane.
+    pub only_editors: Vec<String>,
+    /// Replace file args with their canonical path, resolved against the pane's `cwd`.
+    pub canonicalize_paths: bool,
+    /// Drop `start_suspended` from panes that were rewritten.
+    pub clear_suspended: bool,
+    /// Only collapse a command to the bare editor name if that name is found on `$PATH`.
+    pub verify_editor_exists: bool,
+    /// Directory of the layout being processed, used to resolve paths in panes without a
+    /// `cwd` (and relative `cwd`s).
+    pub layout_dir: Option<PathBuf>,
+    /// What a simplified pane's command is collapsed to.
+    pub command_form: CommandForm,
+    /// Only treat trailing nvim args with one of these extensions as files. Empty means any
+    /// arg that could be a filename.
+    pub file_extensions: Vec<String>,
+    /// Write the children of simplified panes on the pane's line, e.g.
+    /// `pane command="nvim" { args "a.txt"; }`.
+    pub compact: bool,
+    /// How warnings hit while rewriting panes are written to stderr.
+    pub diagnostics: Diagnostics,
+    /// Line endings of rewritten layouts.
+    pub eol: Eol,
+    /// Rewrite simplified nvim panes that open a single file and carry nothing else as
+    /// `pane edit="file"`.
+    pub convert_to_edit_when_single: bool,
+    /// Programs that run the command after their own args, like `mise exec --`. Their prefix
+    /// is kept and the editor after it is simplified.
+    pub launchers: Vec<String>,
+    /// Program to simplify nvim commands with instead of the built-in rules. Each command line
+    /// is written to its stdin, and what it prints becomes the simplified command.
+    pub transform_cmd: Option<PathBuf>,
+}
 
-    if !quiet {
-        print_summary(&changes, verbose, dry_run);
-    }
+/// What the command of a simplified pane is written as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CommandForm {
+    /// The editor's name, e.g. `nvim`
+    #[default]
+    Bare,
+    /// The basename of the original command, e.g. `nvim.exe`
+    Basename,
+    /// The original command, full path included
+    KeepPath,
+}
 
-    // Log to file (only if not dry-run)
-    if !dry_run
-        && !changes.is_empty()
-        && let Ok(mut log_file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("/tmp/nvim-resurrect.log")
-    {
-        let timestamp = Local::now().format("%Y-%m-%d %I:%M:%S %p");
-        let _ = writeln!(
-            log_file,
-            "\n[{}] Processed {} files",
-            timestamp,
-            changes.len()
-        );
-    }
+/// Line endings written to rewritten layouts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Eol {
+    /// The input's dominant line ending
+    #[default]
+    Preserve,
+    /// `\n` everywhere
+    Lf,
+    /// `\r\n` everywhere
+    Crlf,
 }
 
-/// Prints a summary of changes found and applied.
-fn print_summary(changes: &[Changes], verbose: bool, dry_run: bool) {
-    if changes.is_empty() {
-        println!("\nNo changes needed.");
-    } else {
-        println!("\nFound {} file(s) to update.", changes.len());
+/// One level of indentation for lines the tool creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tab,
+}
 
-        if !verbose && !dry_run {
-            println!("Files updated:");
-            for change in changes {
-                println!("  {}", change.file_path);
-            }
-        } else if dry_run && !verbose {
-            println!("Files that would be updated:");
-            for change in changes {
-                println!("  {}", change.file_path);
-            }
+impl Indent {
+    /// The whitespace for one level.
+    pub fn unit(self) -> String {
+        match self {
+            Indent::Spaces(width) => " ".repeat(width),
+            Indent::Tab => "\t".to_string(),
         }
+    }
+}
 
-        if verbose {
-            println!("\nDetailed changes:");
-            for (idx, change) in changes.iter().enumerate() {
-                println!("\n{}. {}", idx + 1, change.file_path);
-                println!("   Original: {}", change.original_command);
-                println!("   Simplified: {}", change.simplified_command);
-            }
+impl std::str::FromStr for Indent {
+    type Err = String;
+
+    /// Parses a number of spaces, or `tab`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("tab") {
+            return Ok(Indent::Tab);
         }
+        s.parse()
+            .map(Indent::Spaces)
+            .map_err(|_| format!("expected a number of spaces or \"tab\", got {:?}", s))
     }
 }
 
-/// Recursively scans directories for session-layout.kdl files.
-fn scan_dir_recursive(dir: &Path, changes: &mut Vec<Changes>, verbose: bool, dry_run: bool) {
-    if let Ok(entries) = std::fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                scan_dir_recursive(&path, changes, verbose, dry_run);
-            } else if path.file_name().and_then(|n| n.to_str()) == Some("session-layout.kdl")
-                && let Some(path_str) = path.to_str()
-            {
-                process_kdl_file(path_str, changes, verbose, dry_run);
-            }
+impl Default for TransformOptions {
+    fn default() -> Self {
+        Self {
+            resolve_shebang: false,
+            args_node: "args".to_string(),
+            args_node_ignore_case: false,
+            ignore_case: cfg!(windows),
+            editors: default_editors(),
+            simplify_other_editors: false,
+            inline_args_to_child: false,
+            indent: None,
+            only_editors: Vec::new(),
+            canonicalize_paths: false,
+            clear_suspended: false,
+            verify_editor_exists: false,
+            layout_dir: None,
+            command_form: CommandForm::Bare,
+            file_extensions: Vec::new(),
+            compact: false,
+            diagnostics: Diagnostics::Text,
+            eol: Eol::Preserve,
+            launchers: Vec::new(),
+            convert_to_edit_when_single: false,
+            transform_cmd: None,
         }
     }
 }
 
-/// Processes a single KDL file, simplifying nvim commands.
-fn process_kdl_file(file_path: &str, changes: &mut Vec<Changes>, verbose: bool, dry_run: bool) {
-    if verbose {
-        if dry_run {
-            println!("Would process: {}", file_path);
+impl TransformOptions {
+    /// Whether a pane child node named `name` holds the pane's args.
+    pub(crate) fn is_args_node(&self, name: &str) -> bool {
+        if self.args_node_ignore_case {
+            name.eq_ignore_ascii_case(&self.args_node)
         } else {
-            println!("Processing: {}", file_path);
+            name == self.args_node
         }
     }
+}
 
-    match std::fs::read_to_string(file_path) {
-        Ok(content) => {
-            let (modified, mut file_changes) = process_kdl_content(&content);
+/// Processes KDL content and simplifies nvim pane commands, and applies direnv wrapping for "dr " tabs.
+/// Returns the modified content and a list of changes made. Content that fails to parse is
+/// returned unchanged.
+pub fn process_kdl_content(content: &str) -> (String, Vec<Changes>) {
+    process_kdl_content_with(content, &TransformOptions::default())
+}
 
-            if !file_changes.is_empty() {
-                // Add file path to all changes from this file
-                for change in &mut file_changes {
-                    change.file_path = file_path.to_string();
-                }
-                changes.extend(file_changes);
+/// Like [`process_kdl_content`], with explicit transform options.
+pub fn process_kdl_content_with(
+    content: &str,
+    options: &TransformOptions,
+) -> (String, Vec<Changes>) {
+    try_process_kdl_content(content, options).unwrap_or_else(|_| (content.to_string(), Vec::new()))
+}
 
-                if !dry_run && let Err(e) = std::fs::write(file_path, &modified) {
-                    eprintln!("Error writing to {}: {}", file_path, e);
-                }
-            }
-        }
-        Err(e) => eprintln!("Error reading {}: {}", file_path, e),
+/// Like [`process_kdl_content_with`], but surfaces KDL parse failures instead of returning the
+/// content unchanged.
+pub fn try_process_kdl_content(
+    content: &str,
+    options: &TransformOptions,
+) -> Result<(String, Vec<Changes>), KdlError> {
+    // Stub layouts have nothing to rewrite; skip parsing them
+    if content.trim().is_empty() {
+        return Ok((content.to_string(), Vec::new()));
     }
-}
 
-/// Processes KDL content and simplifies nvim pane commands, and applies direnv wrapping for "dr " tabs.
-/// Returns the modified content and a list of changes made.
-pub fn process_kdl_content(content: &str) -> (String, Vec<Changes>) {
-    let mut doc: KdlDocument = match content.parse() {
-        Ok(doc) => doc,
-        Err(_) => return (content.to_string(), Vec::new()),
-    };
+    let mut doc: KdlDocument = content.parse()?;
 
     let mut changes = Vec::new();
 
     // Recursively process all nodes to find tabs (handles layout wrapper)
-    process_nodes_recursive(doc.nodes_mut(), &mut changes);
+    process_nodes_recursive(doc.nodes_mut(), options, &mut changes, &mut 0);
+
+    let mut modified = preserve_trailing_newline(content, doc.to_string());
+    if !changes.is_empty() {
+        modified = normalize_line_endings(content, modified, options.eol);
+    }
+    Ok((modified, changes))
+}
+
+/// Rewrites every line ending in `output` to the one `eol` asks for. [`Eol::Preserve`] uses
+/// whichever of `\r\n` and `\n` is more common in `original`.
+fn normalize_line_endings(original: &str, output: String, eol: Eol) -> String {
+    let crlf = match eol {
+        Eol::Lf => false,
+        Eol::Crlf => true,
+        Eol::Preserve => {
+            let crlf_count = original.matches("\r\n").count();
+            crlf_count > original.matches('\n').count() - crlf_count
+        }
+    };
+    if !crlf && !output.contains('\r') {
+        return output;
+    }
+    let lf = output.replace("\r\n", "\n");
+    if crlf { lf.replace('\n', "\r\n") } else { lf }
+}
 
-    (doc.to_string(), changes)
+/// Makes the output end with a newline exactly when the original content did,
+/// so rewrites don't add or drop the final newline of a file.
+fn preserve_trailing_newline(original: &str, mut output: String) -> String {
+    let had_newline = original.ends_with('\n');
+    if had_newline && !output.ends_with('\n') {
+        output.push('\n');
+    } else if !had_newline {
+        while output.ends_with('\n') {
+            output.pop();
+            if output.ends_with('\r') {
+                output.pop();
+            }
+        }
+    }
+    output
 }
 
 /// Recursively processes nodes to find tabs and panes.
 /// This handles the `layout { ... }` wrapper that real session files have.
-fn process_nodes_recursive(nodes: &mut [KdlNode], changes: &mut Vec<Changes>) {
+fn process_nodes_recursive(
+    nodes: &mut [KdlNode],
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+    panes: &mut usize,
+) {
     for node in nodes {
         if node.name().value() == "tab" {
             let is_dr_tab = is_direnv_tab(node);
-            process_panes_in_node(node, is_dr_tab, changes);
-        } else if node.name().value() == "pane" {
-            // Top-level pane (not in a tab) - just apply nvim simplification
-            process_single_pane(node, false, changes);
+            process_panes_in_node(node, is_dr_tab, options, changes, panes);
+        } else if is_pane_node(node) {
+            // Top-level pane or pane template (not in a tab) - just apply nvim simplification
+            process_single_pane(node, false, options, changes, panes);
             // Also recurse into nested panes
             if let Some(children) = node.children_mut() {
-                process_nodes_recursive(children.nodes_mut(), changes);
+                process_nodes_recursive(children.nodes_mut(), options, changes, panes);
             }
         } else if let Some(children) = node.children_mut() {
             // Recurse into layout, swap_tiled_layout, new_tab_template, etc.
-            process_nodes_recursive(children.nodes_mut(), changes);
+            process_nodes_recursive(children.nodes_mut(), options, changes, panes);
         }
     }
 }
 
+/// Checks if a node is a `pane`, or a `pane_template` that can carry a command the same way.
+pub(crate) fn is_pane_node(node: &KdlNode) -> bool {
+    matches!(node.name().value(), "pane" | "pane_template")
+}
+
 /// Checks if a tab node has a name starting with "dr "
 fn is_direnv_tab(node: &KdlNode) -> bool {
     for entry in node.entries() {
-        if entry.name().map(|n| n.value()) == Some("name") {
-            if let Some(name) = entry.value().as_string() {
-                return name.starts_with("dr ");
-            }
+        if entry.name().map(|n| n.value()) == Some("name")
+            && let Some(name) = entry.value().as_string()
+        {
+            return name.starts_with("dr ");
         }
     }
     false
 }
 
 /// Recursively processes all pane nodes within a node
-fn process_panes_in_node(node: &mut KdlNode, is_dr_tab: bool, changes: &mut Vec<Changes>) {
+fn process_panes_in_node(
+    node: &mut KdlNode,
+    is_dr_tab: bool,
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+    panes: &mut usize,
+) {
     // Process children recursively
     if let Some(children) = node.children_mut() {
         for child in children.nodes_mut() {
-            if child.name().value() == "pane" {
-                process_single_pane(child, is_dr_tab, changes);
+            if is_pane_node(child) {
+                process_single_pane(child, is_dr_tab, options, changes, panes);
                 // Also process nested panes
-                process_panes_in_node(child, is_dr_tab, changes);
+                process_panes_in_node(child, is_dr_tab, options, changes, panes);
+            }
+        }
+    }
+}
+
+/// Parses `content` and renders the parser's view of it: one line per node, entry and
+/// children block, indented by depth. Formatting, comments and whitespace are left out.
+pub fn dump_ast(content: &str) -> Result<String, KdlError> {
+    let doc: KdlDocument = content.parse()?;
+    let mut out = String::new();
+    dump_document(&doc, 0, &mut out);
+    Ok(out)
+}
+
+fn dump_document(doc: &KdlDocument, depth: usize, out: &mut String) {
+    for node in doc.nodes() {
+        let indent = "  ".repeat(depth);
+        let ty = node.ty().map(|ty| format!("({})", ty)).unwrap_or_default();
+        out.push_str(&format!("{}node {}{}\n", indent, ty, node.name().value()));
+        for entry in node.entries() {
+            let ty = entry.ty().map(|ty| format!("({})", ty)).unwrap_or_default();
+            match entry.name() {
+                Some(name) => out.push_str(&format!(
+                    "{}  prop {} = {}{}\n",
+                    indent,
+                    name.value(),
+                    ty,
+                    entry.value()
+                )),
+                None => out.push_str(&format!("{}  arg {}{}\n", indent, ty, entry.value())),
             }
         }
+        if let Some(children) = node.children() {
+            out.push_str(&format!("{}  children\n", indent));
+            dump_document(children, depth + 2, out);
+        }
     }
 }
 
-/// Process a single pane node - either apply direnv transform or nvim simplification
-fn process_single_pane(pane: &mut KdlNode, is_dr_tab: bool, changes: &mut Vec<Changes>) {
+/// Process a single pane node - either apply direnv transform or nvim simplification.
+/// `panes` counts the pane nodes seen so far, to number the changes by pane.
+fn process_single_pane(
+    pane: &mut KdlNode,
+    is_dr_tab: bool,
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+    panes: &mut usize,
+) {
+    *panes += 1;
+    let pane_number = *panes;
+
     // Find the command attribute
     let command_value = get_entry_string_value(pane, "command");
     let command = match command_value {
@@ -201,21 +387,212 @@ fn process_single_pane(pane: &mut KdlNode, is_dr_tab: bool, changes: &mut Vec<Ch
     };
 
     // Get existing args from children
-    let existing_args = get_args_from_children(pane);
+    let existing_args = read_args(pane, options);
+    let changes_before = changes.len();
 
     if is_dr_tab {
         // Apply direnv transformation
-        apply_direnv_transform(pane, &command, &existing_args, changes);
+        let runs_nvim = is_nvim(&command, options.ignore_case)
+            || launcher_nvim_prefix(&command, &existing_args, options).is_some();
+        let editor = runs_nvim.then_some("nvim");
+        if editor_selected(options, editor) {
+            apply_direnv_transform(pane, &command, &existing_args, options, changes);
+        }
     } else {
         // Only apply nvim simplification for non-dr tabs
-        if command.contains("nvim") {
-            apply_nvim_simplification(pane, &command, &existing_args, changes);
+        let rules = rules_for(&options.editors);
+        let nvim_enabled = options.editors.iter().any(|e| e == "nvim");
+        if is_nvim(&command, options.ignore_case) && nvim_enabled {
+            if editor_selected(options, Some("nvim")) {
+                apply_nvim_simplification(pane, &command, &existing_args, options, changes);
+            }
+        } else if nvim_enabled
+            && existing_args.is_empty()
+            && let Some(words) = joined_nvim_command(&command, options)
+        {
+            if editor_selected(options, Some("nvim")) {
+                apply_joined_nvim_simplification(pane, &command, &words, options, changes);
+            }
+        } else if nvim_enabled
+            && let Some(prefix_len) = launcher_nvim_prefix(&command, &existing_args, options)
+        {
+            if editor_selected(options, Some("nvim")) {
+                apply_launcher_nvim_simplification(
+                    pane,
+                    &command,
+                    &existing_args,
+                    prefix_len,
+                    options,
+                    changes,
+                );
+            }
+        } else if nvim_enabled
+            && let Some(payload) = shell_nvim_payload(&command, &existing_args, options)
+        {
+            if editor_selected(options, Some("nvim")) {
+                apply_shell_nvim_simplification(
+                    pane,
+                    &command,
+                    &existing_args,
+                    &payload,
+                    options,
+                    changes,
+                );
+            }
+        } else if let Some(rule) = rules
+            .iter()
+            .find(|r| r.matches(&command, options.ignore_case))
+        {
+            if options.simplify_other_editors && editor_selected(options, Some(&rule.name)) {
+                apply_editor_simplification(pane, &command, rule, &existing_args, options, changes);
+            }
+        } else if options.resolve_shebang
+            && let Some(editor) = shebang_editor(&command, &rules, options.ignore_case)
+            && editor_selected(options, Some(&editor))
+        {
+            apply_script_simplification(pane, &command, &editor, &existing_args, options, changes);
         }
     }
+
+    if options.clear_suspended && changes.len() > changes_before {
+        remove_child(pane, "start_suspended");
+    }
+    if options.convert_to_edit_when_single
+        && !is_dr_tab
+        && changes.len() > changes_before
+        && changes
+            .last()
+            .is_some_and(|c| c.editor.as_deref() == Some("nvim"))
+    {
+        convert_to_edit(pane, options);
+    }
+    if options.compact && changes.len() > changes_before {
+        compact_children(pane);
+    }
+    for change in &mut changes[changes_before..] {
+        change.pane = pane_number;
+    }
+}
+
+/// Checks a pane's editor against `options.only_editors`; an empty filter selects every pane
+pub(crate) fn editor_selected(options: &TransformOptions, editor: Option<&str>) -> bool {
+    options.only_editors.is_empty()
+        || editor.is_some_and(|e| options.only_editors.iter().any(|o| o == e))
+}
+
+/// Reads the shebang of an executable script and returns the editor it runs, if any.
+/// Handles both `#!/usr/bin/nvim -l` and `#!/usr/bin/env -S nvim -l`.
+fn shebang_editor(command: &str, rules: &[EditorRule], ignore_case: bool) -> Option<String> {
+    let path = Path::new(command);
+    if !path.is_file() {
+        return None;
+    }
+
+    let file = std::fs::File::open(path).ok()?;
+    let mut first_line = String::new();
+    BufReader::new(file.take(4096))
+        .read_line(&mut first_line)
+        .ok()?;
+
+    let mut tokens = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = tokens.next()?;
+    if interpreter.rsplit('/').next() == Some("env") {
+        interpreter = tokens.find(|t| !t.starts_with('-') && !t.contains('='))?;
+    }
+
+    rules
+        .iter()
+        .find(|r| r.matches(interpreter, ignore_case))
+        .map(|r| r.name.clone())
+}
+
+/// Apply an editor rule to a pane running that editor directly (for non-dr tabs)
+fn apply_editor_simplification(
+    pane: &mut KdlNode,
+    original_command: &str,
+    rule: &EditorRule,
+    existing_args: &[String],
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+) {
+    let full_cmd = if existing_args.is_empty() {
+        original_command.to_string()
+    } else {
+        format!("{} {}", original_command, existing_args.join(" "))
+    };
+
+    let argv: Vec<String> = std::iter::once(original_command.to_string())
+        .chain(existing_args.iter().cloned())
+        .collect();
+    let mut new_args =
+        format_with_rules(&argv, std::slice::from_ref(rule), options.ignore_case).split_off(1);
+    if options.canonicalize_paths {
+        new_args = canonicalize_args(pane, new_args, options);
+    }
+    let command = collapsed_command(original_command, &rule.name, options);
+    if command == original_command && new_args == existing_args {
+        return;
+    }
+    let formatted = format!("{} {}", command, new_args.join(" "))
+        .trim_end()
+        .to_string();
+
+    set_entry_string_value(pane, "command", command);
+    write_args(pane, &new_args, options);
+
+    changes.push(Changes {
+        file_path: String::new(),
+        original_command: full_cmd,
+        simplified_command: formatted,
+        editor: Some(rule.name.clone()),
+        files: file_args(&new_args),
+        pane: 0,
+    });
+}
+
+/// Clean the args of a wrapper script whose shebang runs an editor, keeping the script as the command
+fn apply_script_simplification(
+    pane: &mut KdlNode,
+    script: &str,
+    editor: &str,
+    existing_args: &[String],
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+) {
+    let argv: Vec<String> = std::iter::once(editor.to_string())
+        .chain(existing_args.iter().cloned())
+        .collect();
+    let new_args =
+        format_with_rules(&argv, &rules_for(&options.editors), options.ignore_case).split_off(1);
+
+    if new_args == existing_args {
+        return;
+    }
+
+    write_args(pane, &new_args, options);
+
+    changes.push(Changes {
+        file_path: String::new(),
+        original_command: format!("{} {}", script, existing_args.join(" ")),
+        simplified_command: format!("{} {}", script, new_args.join(" "))
+            .trim_end()
+            .to_string(),
+        editor: Some(editor.to_string()),
+        files: file_args(&new_args),
+        pane: 0,
+    });
+}
+
+/// The args that name files, leaving out flags and `+` startup commands.
+fn file_args(args: &[String]) -> Vec<String> {
+    args.iter()
+        .filter(|arg| !arg.starts_with('-') && !arg.starts_with('+'))
+        .cloned()
+        .collect()
 }
 
 /// Get a string value from a named entry (attribute)
-fn get_entry_string_value(node: &KdlNode, name: &str) -> Option<String> {
+pub(crate) fn get_entry_string_value(node: &KdlNode, name: &str) -> Option<String> {
     for entry in node.entries() {
         if entry.name().map(|n| n.value()) == Some(name) {
             return entry.value().as_string().map(|s| s.to_string());
@@ -224,11 +601,55 @@ fn get_entry_string_value(node: &KdlNode, name: &str) -> Option<String> {
     None
 }
 
-/// Extract args values from the "args" child node
-fn get_args_from_children(pane: &KdlNode) -> Vec<String> {
+/// Read a pane's args from its args child node, or else from an inline `args="..."` property,
+/// split on whitespace
+pub(crate) fn read_args(pane: &KdlNode, options: &TransformOptions) -> Vec<String> {
+    if has_inline_args(pane, options) {
+        return get_entry_string_value(pane, &options.args_node)
+            .map(|args| args.split_whitespace().map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+    }
+    get_args_from_children(pane, options)
+}
+
+/// Write a pane's args back in the form it stores them in: an inline property stays inline
+/// (unless `options.inline_args_to_child`), otherwise the args child node is used
+fn write_args(pane: &mut KdlNode, args: &[String], options: &TransformOptions) {
+    if has_inline_args(pane, options) {
+        if options.inline_args_to_child || args.is_empty() {
+            remove_entry(pane, &options.args_node);
+        } else {
+            set_entry_string_value(pane, &options.args_node, &args.join(" "));
+            return;
+        }
+    }
+
+    if args.is_empty() {
+        if let Some(children) = pane.children_mut() {
+            children
+                .nodes_mut()
+                .retain(|n| !options.is_args_node(n.name().value()));
+        }
+    } else {
+        set_args_in_children(pane, args, options);
+    }
+}
+
+/// Checks if a pane stores its args as an inline property rather than a child node
+fn has_inline_args(pane: &KdlNode, options: &TransformOptions) -> bool {
+    let has_child = pane.children().is_some_and(|c| {
+        c.nodes()
+            .iter()
+            .any(|n| options.is_args_node(n.name().value()))
+    });
+    !has_child && get_entry_string_value(pane, &options.args_node).is_some()
+}
+
+/// Extract args values from the args child node (named `args_node`)
+fn get_args_from_children(pane: &KdlNode, options: &TransformOptions) -> Vec<String> {
     if let Some(children) = pane.children() {
         for child in children.nodes() {
-            if child.name().value() == "args" {
+            if options.is_args_node(child.name().value()) {
                 return child
                     .entries()
                     .iter()
@@ -246,6 +667,7 @@ fn apply_direnv_transform(
     pane: &mut KdlNode,
     original_command: &str,
     existing_args: &[String],
+    options: &TransformOptions,
     changes: &mut Vec<Changes>,
 ) {
     // Skip if already direnv wrapped
@@ -253,43 +675,31 @@ fn apply_direnv_transform(
         return;
     }
 
-    // Simplify command if it's an nvim command
-    let simplified_cmd = if original_command.contains("nvim") {
-        // Build full command with args for nvim simplification
-        let full_cmd = if existing_args.is_empty() {
-            original_command.to_string()
-        } else {
-            format!("{} {}", original_command, existing_args.join(" "))
-        };
-        let formatted = format_nvim(&full_cmd);
-        // Extract just the command part (nvim) and args separately
-        formatted
-    } else {
-        original_command.to_string()
+    // Simplify command if it's an nvim command, keeping just the file args. Behind `env` or a
+    // launcher, the prefix (e.g. `NVIM_APPNAME=work`) is kept in front of the cleaned nvim part.
+    let launched_nvim = || {
+        let prefix_len = launcher_nvim_prefix(original_command, existing_args, options)?;
+        let (prefix, rest) = existing_args.split_at(prefix_len);
+        let (nvim, rest) = rest.split_first()?;
+        let (nvim, files) = simplified_nvim(nvim, rest, options)?;
+        let opened = file_args(&files);
+        let mut args = prefix.to_vec();
+        args.push(nvim);
+        args.extend(files);
+        Some((args, opened))
     };
-
-    // Parse simplified_cmd to get the actual command name and any file args
-    let (cmd_name, nvim_file_args) = if simplified_cmd.starts_with("nvim ") {
-        (
-            "nvim".to_string(),
-            simplified_cmd
-                .strip_prefix("nvim ")
-                .unwrap_or("")
-                .split_whitespace()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>(),
-        )
-    } else if simplified_cmd == "nvim" {
-        ("nvim".to_string(), Vec::new())
-    } else {
-        // // Non-nvim command - use the last path component as the command name
-        // let cmd_name = std::path::Path::new(&simplified_cmd)
-        //     .file_name()
-        //     .and_then(|n| n.to_str())
-        //     .unwrap_or(&simplified_cmd)
-        //     .to_string();
-        // (cmd_name, Vec::new())
-        (simplified_cmd.to_string(), Vec::new())
+    let direct_nvim = is_nvim(original_command, options.ignore_case)
+        .then(|| simplified_nvim(original_command, existing_args, options))
+        .flatten();
+    let (cmd_name, nvim_file_args, files, runs_nvim) = match direct_nvim {
+        Some((command, files)) => {
+            let opened = file_args(&files);
+            (command, files, opened, true)
+        }
+        None => match launched_nvim() {
+            Some((args, opened)) => (original_command.to_string(), args, opened, true),
+            None => (original_command.to_string(), Vec::new(), Vec::new(), false),
+        },
     };
 
     // Build new args: "exec" "." <cmd_name> [args...]
@@ -306,8 +716,8 @@ fn apply_direnv_transform(
     // Update command attribute to "direnv"
     set_entry_string_value(pane, "command", "direnv");
 
-    // Update args child node
-    set_args_in_children(pane, &new_args);
+    // Update args
+    write_args(pane, &new_args, options);
 
     // Record the change
     let original_desc = if existing_args.is_empty() {
@@ -319,6 +729,9 @@ fn apply_direnv_transform(
         file_path: String::new(),
         original_command: original_desc,
         simplified_command: format!("direnv {}", new_args.join(" ")),
+        editor: runs_nvim.then(|| "nvim".to_string()),
+        files,
+        pane: 0,
     });
 }
 
@@ -327,6 +740,7 @@ fn apply_nvim_simplification(
     pane: &mut KdlNode,
     original_command: &str,
     existing_args: &[String],
+    options: &TransformOptions,
     changes: &mut Vec<Changes>,
 ) {
     // Build full command with args
@@ -336,133 +750,601 @@ fn apply_nvim_simplification(
         format!("{} {}", original_command, existing_args.join(" "))
     };
 
-    let formatted = format_nvim(&full_cmd);
+    let Some((command, mut files)) = simplified_nvim(original_command, existing_args, options)
+    else {
+        return;
+    };
+    if options.canonicalize_paths {
+        files = canonicalize_args(pane, files, options);
+    }
+    let formatted = format!("{} {}", command, files.join(" "))
+        .trim_end()
+        .to_string();
 
     // Only apply if there's a change
     if formatted == full_cmd {
         return;
     }
 
-    // Parse the formatted result - "nvim file1 file2"
-    let files: Vec<String> = formatted
-        .strip_prefix("nvim ")
-        .unwrap_or("")
-        .split_whitespace()
-        .map(|s| s.to_string())
-        .collect();
-
     // Update command attribute to "nvim"
-    set_entry_string_value(pane, "command", "nvim");
+    set_entry_string_value(pane, "command", &command);
 
-    // Update args child node
-    if !files.is_empty() {
-        set_args_in_children(pane, &files);
-    } else {
-        remove_args_from_children(pane);
-    }
+    // Update args
+    write_args(pane, &files, options);
 
     changes.push(Changes {
         file_path: String::new(),
         original_command: full_cmd,
         simplified_command: formatted,
+        editor: Some("nvim".to_string()),
+        files: file_args(&files),
+        pane: 0,
     });
 }
 
-/// Set a string value for a named entry (attribute)
-fn set_entry_string_value(node: &mut KdlNode, name: &str, value: &str) {
-    // Use the node's insert method which handles finding/replacing entries properly
-    node.insert(name, value);
+/// The command and args to simplify the nvim invocation `nvim rest` to: the words
+/// `options.transform_cmd` prints if it is set, or else the collapsed command and the args the
+/// built-in rules keep. `None` leaves the pane as is; a failing program, or one that prints
+/// nothing, also gets a warning.
+fn simplified_nvim(
+    nvim: &str,
+    rest: &[String],
+    options: &TransformOptions,
+) -> Option<(String, Vec<String>)> {
+    let Some(program) = &options.transform_cmd else {
+        let files = nvim_command_args(nvim, rest, options)?;
+        return Some((collapsed_command(nvim, "nvim", options).to_string(), files));
+    };
+
+    let line = std::iter::once(nvim)
+        .chain(rest.iter().map(String::as_str))
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let words = match run_transform_cmd(program, &line) {
+        Ok(output) => shell_words(&output).filter(|words| !words.is_empty()),
+        Err(e) => {
+            options.diagnostics.warn(
+                &format!("{} failed on {}: {}, skipping", program.display(), line, e),
+                None,
+            );
+            return None;
+        }
+    };
+    let Some(mut words) = words else {
+        options.diagnostics.warn(
+            &format!(
+                "{} printed no command for {}, skipping",
+                program.display(),
+                line
+            ),
+            None,
+        );
+        return None;
+    };
+    let command = words.remove(0);
+    Some((command, words))
 }
 
-/// Set args values in the "args" child node
-fn set_args_in_children(pane: &mut KdlNode, args: &[String]) {
-    // Ensure children document exists
-    if pane.children().is_none() {
-        pane.set_children(KdlDocument::new());
+/// Runs `program` with `line` on its stdin, returning its stdout. A non-zero exit is an error.
+fn run_transform_cmd(program: &Path, line: &str) -> std::io::Result<String> {
+    let mut child = std::process::Command::new(program)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", line)?;
     }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "exited with {}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
 
-    // Find existing args node and its formatting
-    let (found_idx, leading_text) = pane
-        .children()
-        .map(|c| {
-            c.nodes()
-                .iter()
-                .enumerate()
-                .find(|(_, child)| child.name().value() == "args")
-                .map(|(idx, node)| (Some(idx), node.leading().map(|s| s.to_string())))
-        })
-        .flatten()
-        .unwrap_or((None, None));
-
-    // If no existing args node, try to get formatting from another child node
-    let leading = leading_text.or_else(|| {
-        pane.children()
-            .and_then(|c| c.nodes().first())
-            .and_then(|n| n.leading().map(|s| s.to_string()))
-    });
-
-    // Create new args node with proper formatting
-    let mut args_node = KdlNode::new("args");
-    if let Some(lead) = leading {
-        args_node.set_leading(lead);
+/// For a pane whose `command` holds a whole nvim invocation, such as
+/// `command="/path/nvim --cmd x file.txt"` with no args, the command split into words.
+fn joined_nvim_command(command: &str, options: &TransformOptions) -> Option<Vec<String>> {
+    if !command.contains(char::is_whitespace) {
+        return None;
     }
-    for arg in args {
-        args_node
-            .entries_mut()
-            .push(KdlEntry::new(KdlValue::String(arg.clone())));
+    let words = shell_words(command)?;
+    is_nvim(words.first()?, options.ignore_case).then_some(words)
+}
+
+/// Apply nvim simplification to a pane with the whole invocation in `command`, splitting it
+/// into the collapsed command and an args node with the files
+fn apply_joined_nvim_simplification(
+    pane: &mut KdlNode,
+    original_command: &str,
+    words: &[String],
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+) {
+    let Some((nvim, rest)) = words.split_first() else {
+        return;
+    };
+    let Some((command, mut files)) = simplified_nvim(nvim, rest, options) else {
+        return;
+    };
+    if options.canonicalize_paths {
+        files = canonicalize_args(pane, files, options);
     }
 
-    if let Some(children) = pane.children_mut() {
-        if let Some(idx) = found_idx {
-            // Replace existing args node
-            children.nodes_mut()[idx] = args_node;
-        } else {
-            // Insert at the beginning - need to remove leading newline from the node that was first
-            // to avoid double newlines, but keep its indentation
-            if let Some(first_node) = children.nodes_mut().first_mut() {
-                let new_leading = first_node
-                    .leading()
-                    .map(|l| l.trim_start_matches('\n').to_string());
-                if let Some(trimmed) = new_leading {
-                    first_node.set_leading(trimmed);
-                }
-            }
-            children.nodes_mut().insert(0, args_node);
-        }
+    // Only apply if there's a change; splitting the words into command and args is one
+    if command == original_command && files.is_empty() {
+        return;
     }
+
+    set_entry_string_value(pane, "command", &command);
+    write_args(pane, &files, options);
+
+    changes.push(Changes {
+        file_path: String::new(),
+        original_command: original_command.to_string(),
+        simplified_command: format!("{} {}", command, files.join(" "))
+            .trim_end()
+            .to_string(),
+        editor: Some("nvim".to_string()),
+        files: file_args(&files),
+        pane: 0,
+    });
 }
 
-/// Remove args node from children
-fn remove_args_from_children(pane: &mut KdlNode) {
-    if let Some(children) = pane.children_mut() {
-        children.nodes_mut().retain(|n| n.name().value() != "args");
+/// For a pane running `env KEY=VALUE... nvim ...`, or nvim behind one of `options.launchers`,
+/// the number of args before nvim.
+fn launcher_nvim_prefix(
+    command: &str,
+    args: &[String],
+    options: &TransformOptions,
+) -> Option<usize> {
+    let program = command.rsplit('/').next().unwrap_or(command);
+    if options.launchers.iter().any(|l| l == program) {
+        return args
+            .iter()
+            .position(|arg| is_nvim(arg, options.ignore_case));
+    }
+    if program != "env" {
+        return None;
     }
+    let prefix_len = args
+        .iter()
+        .take_while(|arg| !arg.starts_with('-') && arg.contains('='))
+        .count();
+    let program = args.get(prefix_len)?;
+    is_nvim(program, options.ignore_case).then_some(prefix_len)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use pretty_assertions::assert_eq;
+/// Apply nvim simplification behind an `env KEY=VALUE...` or launcher prefix, keeping the
+/// prefix and cleaning the nvim part
+fn apply_launcher_nvim_simplification(
+    pane: &mut KdlNode,
+    command: &str,
+    existing_args: &[String],
+    prefix_len: usize,
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+) {
+    let (prefix, nvim_args) = existing_args.split_at(prefix_len);
+    let Some((nvim, rest)) = nvim_args.split_first() else {
+        return;
+    };
+    let Some((nvim, files)) = simplified_nvim(nvim, rest, options) else {
+        return;
+    };
 
-    #[test]
-    fn test_process_kdl_content_single_file() {
-        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
-            args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
-            start_suspended true
-        }"#;
+    let opened = file_args(&files);
+    let mut new_args = prefix.to_vec();
+    new_args.push(nvim);
+    new_args.extend(files);
+    if new_args == existing_args {
+        return;
+    }
 
-        let (result, changes) = process_kdl_content(input);
+    write_args(pane, &new_args, options);
 
-        // Should have command="nvim" with args "file.txt"
-        assert!(result.contains(r#"command="nvim""#));
-        assert!(result.contains(r#"args "file.txt""#));
-        assert!(result.contains("start_suspended true"));
-        assert_eq!(changes.len(), 1);
+    changes.push(Changes {
+        file_path: String::new(),
+        original_command: format!("{} {}", command, existing_args.join(" ")),
+        simplified_command: format!("{} {}", command, new_args.join(" ")),
+        editor: Some("nvim".to_string()),
+        files: opened,
+        pane: 0,
+    });
+}
+
+/// Shells whose `-c` payload is checked for an nvim invocation.
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh"];
+
+/// For a pane running `sh -c "[exec] nvim ..."`, the payload split into words, with a leading
+/// `exec` kept as the first word.
+fn shell_nvim_payload(
+    command: &str,
+    args: &[String],
+    options: &TransformOptions,
+) -> Option<Vec<String>> {
+    let shell = command.rsplit('/').next().unwrap_or(command);
+    if !SHELLS.contains(&shell) {
+        return None;
+    }
+    let [flag, payload] = args else {
+        return None;
+    };
+    if flag != "-c" {
+        return None;
     }
+    let words = shell_words(payload)?;
+    let program = match words.first().map(String::as_str) {
+        Some("exec") => words.get(1)?,
+        _ => words.first()?,
+    };
+    is_nvim(program, options.ignore_case).then_some(words)
+}
 
-    #[test]
-    fn test_process_kdl_content_multiple_files() {
-        let input = r#"
+/// Apply nvim simplification inside a shell's `-c` payload, keeping the shell, the `-c` flag
+/// and a leading `exec`
+fn apply_shell_nvim_simplification(
+    pane: &mut KdlNode,
+    command: &str,
+    existing_args: &[String],
+    payload: &[String],
+    options: &TransformOptions,
+    changes: &mut Vec<Changes>,
+) {
+    let (exec, nvim_words) = match payload.split_first() {
+        Some((first, rest)) if first == "exec" => (Some(first), rest),
+        _ => (None, payload),
+    };
+    let Some((nvim, rest)) = nvim_words.split_first() else {
+        return;
+    };
+    let Some((nvim, files)) = simplified_nvim(nvim, rest, options) else {
+        return;
+    };
+
+    let mut words: Vec<String> = exec.into_iter().cloned().collect();
+    words.push(shell_quote(&nvim));
+    words.extend(files.iter().map(|f| shell_quote(f)));
+    let new_args = vec!["-c".to_string(), words.join(" ")];
+    if new_args == existing_args {
+        return;
+    }
+
+    write_args(pane, &new_args, options);
+
+    changes.push(Changes {
+        file_path: String::new(),
+        original_command: format!("{} {}", command, existing_args.join(" ")),
+        simplified_command: format!("{} {}", command, new_args.join(" ")),
+        editor: Some("nvim".to_string()),
+        files: file_args(&files),
+        pane: 0,
+    });
+}
+
+/// Splits a shell command line into words, honouring single and double quotes and backslash
+/// escapes. Returns `None` for an unterminated quote.
+fn shell_words(line: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => word.get_or_insert_default().push(chars.next()?),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => word.push(chars.next()?),
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    Some(words)
+}
+
+/// Quotes a word for a shell command line, if it needs it.
+fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-+./:,=@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Simplified args for a pane running nvim, or `None` if the command isn't nvim.
+/// Works on the args as separate strings, so an arg like `"+normal! Gg"` stays one arg.
+fn nvim_command_args(
+    command: &str,
+    args: &[String],
+    options: &TransformOptions,
+) -> Option<Vec<String>> {
+    let mut tokens = vec![command];
+    tokens.extend(args.iter().map(String::as_str));
+    let is_file = |arg: &str| {
+        if options.file_extensions.is_empty() {
+            could_be_filename(arg)
+        } else {
+            has_extension(arg, &options.file_extensions, options.ignore_case)
+        }
+    };
+    nvim_file_args_matching(&tokens, options.ignore_case, is_file)
+        .map(|files| files.into_iter().map(str::to_string).collect())
+}
+
+/// The command to write for a pane running `editor`, in the shape `options.command_form` asks
+/// for. With `options.verify_editor_exists`, the original command is kept if the collapsed name
+/// isn't found on `$PATH`.
+fn collapsed_command<'a>(
+    original: &'a str,
+    editor: &'a str,
+    options: &TransformOptions,
+) -> &'a str {
+    let collapsed = match options.command_form {
+        CommandForm::Bare => editor,
+        CommandForm::Basename => original.rsplit(['/', '\\']).next().unwrap_or(original),
+        CommandForm::KeepPath => original,
+    };
+    if options.verify_editor_exists && original != collapsed && find_in_path(collapsed).is_none() {
+        options.diagnostics.warn(
+            &format!("{} is not on PATH, keeping {}", collapsed, original),
+            None,
+        );
+        return original;
+    }
+    collapsed
+}
+
+/// Replaces file args with their canonical path, resolved against the pane's `cwd` attribute
+/// (itself relative to `options.layout_dir`), or `options.layout_dir` if the pane has none.
+/// Flags and `+` commands are kept, and so are files that don't exist, with a warning.
+fn canonicalize_args(pane: &KdlNode, args: Vec<String>, options: &TransformOptions) -> Vec<String> {
+    let layout_dir = options.layout_dir.clone().unwrap_or_default();
+    let base = match get_entry_string_value(pane, "cwd") {
+        Some(cwd) => layout_dir.join(cwd),
+        None => layout_dir,
+    };
+
+    args.into_iter()
+        .map(|arg| {
+            if arg.starts_with('-') || arg.starts_with('+') {
+                return arg;
+            }
+            let path = base.join(&arg);
+            match std::fs::canonicalize(&path) {
+                Ok(canonical) => canonical.display().to_string(),
+                Err(_) => {
+                    options.diagnostics.warn(
+                        &format!("{} not found, leaving {} as is", path.display(), arg),
+                        None,
+                    );
+                    arg
+                }
+            }
+        })
+        .collect()
+}
+
+/// Set a string value for a named entry (attribute)
+fn set_entry_string_value(node: &mut KdlNode, name: &str, value: &str) {
+    // Use the node's insert method which handles finding/replacing entries properly
+    node.insert(name, value);
+}
+
+/// Remove the property `name`. Unlike `KdlNode::remove`, matches parsed names regardless of
+/// how they were written (`args` vs `"args"`).
+fn remove_entry(node: &mut KdlNode, name: &str) {
+    node.entries_mut()
+        .retain(|e| e.name().map(|n| n.value()) != Some(name));
+}
+
+/// Set args values in the args child node (named `args_node`), keeping an existing node's name
+fn set_args_in_children(pane: &mut KdlNode, args: &[String], options: &TransformOptions) {
+    // Ensure children document exists, opening it a space after the pane's last entry
+    if pane.children().is_none() {
+        pane.set_children(KdlDocument::new());
+        if pane.before_children().is_none_or(str::is_empty) {
+            pane.set_before_children(" ");
+        }
+    }
+
+    // Find existing args node and its formatting
+    let (found_idx, name, leading_text, trailing_text) = pane
+        .children()
+        .and_then(|c| {
+            c.nodes()
+                .iter()
+                .enumerate()
+                .find(|(_, child)| options.is_args_node(child.name().value()))
+                .map(|(idx, node)| {
+                    (
+                        Some(idx),
+                        node.name().value().to_string(),
+                        node.leading().map(|s| s.to_string()),
+                        node.trailing().map(|s| s.to_string()),
+                    )
+                })
+        })
+        .unwrap_or((None, options.args_node.clone(), None, None));
+
+    // If no existing args node, indent one level past the pane when an indent is configured,
+    // otherwise try to get formatting from another child node
+    let leading = leading_text.or_else(|| match options.indent {
+        Some(indent) => {
+            let pane_indent = pane
+                .leading()
+                .map(|l| l.rsplit('\n').next().unwrap_or(l))
+                .unwrap_or("");
+            Some(format!("\n{}{}", pane_indent, indent.unit()))
+        }
+        None => pane
+            .children()
+            .and_then(|c| c.nodes().first())
+            .and_then(|n| n.leading().map(|s| s.to_string())),
+    });
+
+    // Create new args node with proper formatting
+    let mut new_node = KdlNode::new(name);
+    if let Some(lead) = leading {
+        new_node.set_leading(lead);
+    }
+    // Keep the replaced node's terminator, e.g. `;` in `{ args "a.txt"; }`
+    if let Some(trail) = trailing_text {
+        new_node.set_trailing(trail);
+    }
+    for arg in args {
+        new_node
+            .entries_mut()
+            .push(KdlEntry::new(KdlValue::String(arg.clone())));
+    }
+
+    if let Some(children) = pane.children_mut() {
+        if let Some(idx) = found_idx {
+            // Replace existing args node
+            children.nodes_mut()[idx] = new_node;
+        } else {
+            // Insert at the beginning - need to remove leading newline from the node that was first
+            // to avoid double newlines, but keep its indentation
+            if let Some(first_node) = children.nodes_mut().first_mut() {
+                let new_leading = first_node
+                    .leading()
+                    .map(|l| l.trim_start_matches('\n').to_string());
+                if let Some(trimmed) = new_leading {
+                    first_node.set_leading(trimmed);
+                }
+            }
+            children.nodes_mut().insert(0, new_node);
+        }
+    }
+}
+
+/// Remove child nodes named `name`
+fn remove_child(pane: &mut KdlNode, name: &str) {
+    if let Some(children) = pane.children_mut() {
+        children.nodes_mut().retain(|n| n.name().value() != name);
+    }
+}
+
+/// Rewrites `pane command="nvim" { args "file"; }` as `pane edit="file"`. Panes with any other
+/// attribute, child node or arg (flags and `+` commands included) are left in args form.
+fn convert_to_edit(pane: &mut KdlNode, options: &TransformOptions) {
+    let command_only = matches!(
+        pane.entries(),
+        [entry] if entry.name().map(|n| n.value()) == Some("command")
+            && entry.value().as_string() == Some("nvim")
+    );
+    let file = match pane.children().map(|c| c.nodes()) {
+        Some([args]) if options.is_args_node(args.name().value()) => match args.entries() {
+            [entry] if entry.name().is_none() => entry.value().as_string().map(str::to_string),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(file) = file.filter(|f| command_only && !f.starts_with(['-', '+'])) else {
+        return;
+    };
+
+    remove_entry(pane, "command");
+    pane.insert("edit", file);
+    *pane.children_mut() = None;
+}
+
+/// Puts a pane's children on the pane's line, each ending in `;` so the block stays valid KDL.
+/// Panes whose children carry comments are left as they are.
+fn compact_children(pane: &mut KdlNode) {
+    let Some(children) = pane.children_mut() else {
+        return;
+    };
+    let has_comments = children.nodes().iter().any(|n| {
+        [n.leading(), n.trailing()]
+            .into_iter()
+            .flatten()
+            .any(|s| !s.trim().is_empty() && s.trim() != ";")
+    });
+    if has_comments || children.nodes().is_empty() {
+        return;
+    }
+    for node in children.nodes_mut() {
+        node.set_leading(" ");
+        node.set_trailing(";");
+    }
+    children.set_leading("");
+    children.set_trailing(" ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::default_rules;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_process_kdl_content_single_file() {
+        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+            args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+            start_suspended true
+        }"#;
+
+        let (result, changes) = process_kdl_content(input);
+
+        // Should have command="nvim" with args "file.txt"
+        assert!(result.contains(r#"command="nvim""#));
+        assert!(result.contains(r#"args "file.txt""#));
+        assert!(result.contains("start_suspended true"));
+        assert_eq!(changes.len(), 1);
+    }
+
+    #[test]
+    fn test_clear_suspended() {
+        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+    start_suspended true
+}
+pane command="bacon" {
+    start_suspended true
+}"#;
+        let expected = r#"pane command="nvim" {
+    args "file.txt"
+}
+pane command="bacon" {
+    start_suspended true
+}"#;
+        let options = TransformOptions {
+            clear_suspended: true,
+            ..Default::default()
+        };
+
+        let (result, changes) = process_kdl_content_with(input, &options);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+
+        let (result, _) = process_kdl_content(input);
+        assert_eq!(result.matches("start_suspended true").count(), 2);
+    }
+
+    #[test]
+    fn test_process_kdl_content_multiple_files() {
+        let input = r#"
             pane command="/home/zach/.nix-profile/bin/nvim" focus=true size="50%" {
                 args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.opt.runtimepath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.g.loaded_node_provider=0;vim.g.loaded_perl_provider=0;vim.g.loaded_python_provider=0;vim.g.loaded_python3_provider=0;vim.g.ruby_host_prog='/nix/store/4pm6h00i8jizd1vcfh90gkfsipd634rc-neovim-providers/bin/neovim-ruby-host'" "--cmd" "lua vim.opt.packpath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.opt.runtimepath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.g.loaded_node_provider=0;vim.g.loaded_perl_provider=0;vim.g.loaded_python_provider=0;vim.g.loaded_python3_provider=0;vim.g.ruby_host_prog='/nix/store/4pm6h00i8jizd1vcfh90gkfsipd634rc-neovim-providers/bin/neovim-ruby-host'" "--cmd" "lua vim.opt.packpath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.opt.runtimepath:prepend('/nix/store/7fcfmii0vli2ncrgw8phdj1r7zcxf0fc-mnw-configDir');vim.g.loaded_node_provider=0;vim.g.loaded_perl_provider=0;vim.g.loaded_python_provider=0;vim.g.loaded_python3_provider=0;vim.g.ruby_host_prog='/nix/store/4pm6h00i8jizd1vcfh90gkfsipd634rc-neovim-providers/bin/neovim-ruby-host'" "file1.rs" "file2.rs"
                 start_suspended true
@@ -513,6 +1395,1271 @@ mod tests {
         assert_eq!(changes.len(), 0);
     }
 
+    #[test]
+    fn test_process_kdl_content_keeps_missing_trailing_newline() {
+        let input = r#"pane command="/usr/bin/nvim" {
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+
+        assert_eq!(changes.len(), 1);
+        assert!(result.contains(r#"args "file.txt""#));
+        assert!(!result.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_process_kdl_content_keeps_trailing_newline() {
+        let input = r#"pane command="/usr/bin/nvim" {
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+}
+"#;
+
+        let (result, changes) = process_kdl_content(input);
+
+        assert_eq!(changes.len(), 1);
+        assert!(result.contains(r#"args "file.txt""#));
+        assert!(result.ends_with("}\n"));
+        assert!(!result.ends_with("\n\n"));
+    }
+
+    #[test]
+    fn test_shebang_editor_detection() {
+        let temp = tempfile::tempdir().unwrap();
+        let cases = [
+            ("env_nvim", "#!/usr/bin/env -S nvim -l\n", Some("nvim")),
+            ("direct_vim", "#!/usr/bin/vim -u NONE\n", Some("vim")),
+            ("bash", "#!/usr/bin/env bash\nexec nvim \"$@\"\n", None),
+            ("no_shebang", "nvim\n", None),
+        ];
+
+        for (name, contents, expected) in cases {
+            let script = temp.path().join(name);
+            std::fs::write(&script, contents).unwrap();
+            assert_eq!(
+                shebang_editor(script.to_str().unwrap(), &default_rules(), false).as_deref(),
+                expected,
+                "Failed on script: {name}"
+            );
+        }
+        assert_eq!(
+            shebang_editor("/nonexistent/script", &default_rules(), false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_shebang_cleans_script_args() {
+        let temp = tempfile::tempdir().unwrap();
+        let script = temp.path().join("edit");
+        std::fs::write(&script, "#!/usr/bin/env -S nvim -l\n").unwrap();
+        let script = script.to_str().unwrap();
+
+        let input = format!(
+            r#"pane command="{script}" {{
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "notes.md"
+    start_suspended true
+}}"#
+        );
+        let expected = format!(
+            r#"pane command="{script}" {{
+    args "notes.md"
+    start_suspended true
+}}"#
+        );
+
+        let options = TransformOptions {
+            resolve_shebang: true,
+            ..Default::default()
+        };
+        let (result, changes) = process_kdl_content_with(&input, &options);
+        dbg!(&result);
+        dbg!(&changes);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+
+        // Off by default
+        let (result, changes) = process_kdl_content(&input);
+        assert_eq!(result, input);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_process_kdl_content_keeps_plus_command() {
+        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "+normal! Gg" "file.txt"
+    start_suspended true
+}"#;
+        let expected = r#"pane command="nvim" {
+    args "+normal! Gg" "file.txt"
+    start_suspended true
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes[0].simplified_command, "nvim +normal! Gg file.txt");
+    }
+
+    #[test]
+    fn test_process_kdl_content_braces_in_quoted_arg() {
+        let input = r#"layout {
+    pane command="/home/zach/.nix-profile/bin/nvim" {
+        args "--cmd" "lua require('lazy').setup({ spec = { { import = 'plugins' } } })" "file.txt"
+        start_suspended true
+    }
+    pane command="bacon" {
+        args "clippy"
+    }
+}"#;
+        let expected = r#"layout {
+    pane command="nvim" {
+        args "file.txt"
+        start_suspended true
+    }
+    pane command="bacon" {
+        args "clippy"
+    }
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim file.txt");
+    }
+
+    #[test]
+    fn test_inline_args_property() {
+        let input = r#"pane command="/path/nvim" args="--cmd x=1 file.txt" size="50%""#;
+
+        let (result, changes) = process_kdl_content(input);
+        assert_eq!(result, r#"pane command="nvim" args="file.txt" size="50%""#);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].original_command, "/path/nvim --cmd x=1 file.txt");
+        assert_eq!(changes[0].simplified_command, "nvim file.txt");
+
+        let options = TransformOptions {
+            inline_args_to_child: true,
+            ..Default::default()
+        };
+        let (result, _) = process_kdl_content_with(input, &options);
+        let doc: KdlDocument = result.parse().unwrap();
+        let pane = &doc.nodes()[0];
+        assert_eq!(get_entry_string_value(pane, "args"), None);
+        assert_eq!(get_entry_string_value(pane, "size").as_deref(), Some("50%"));
+        assert_eq!(get_args_from_children(pane, &options), vec!["file.txt"]);
+    }
+
+    #[test]
+    fn test_indent_for_new_args_node() {
+        let input = r#"layout {
+    tab name="dr project" {
+        pane command="bacon" {
+            start_suspended true
+        }
+    }
+}"#;
+        let options = TransformOptions {
+            indent: Some(Indent::Spaces(2)),
+            ..Default::default()
+        };
+
+        let (result, _) = process_kdl_content_with(input, &options);
+        dbg!(&result);
+        assert!(result.contains("\n          args \"exec\" \".\" \"bacon\"\n"));
+
+        // Without an indent, the new node follows its sibling
+        let (result, _) = process_kdl_content(input);
+        assert!(result.contains("\n            args \"exec\" \".\" \"bacon\"\n"));
+
+        assert_eq!("tab".parse(), Ok(Indent::Tab));
+        assert_eq!("4".parse(), Ok(Indent::Spaces(4)));
+        assert!("wide".parse::<Indent>().is_err());
+    }
+
+    #[test]
+    fn test_multiple_layouts() {
+        let input = r#"layout {
+    tab name="main" {
+        pane command="/home/zach/.nix-profile/bin/nvim" {
+            args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+        }
+    }
+}
+layout "alt" {
+    pane command="/home/zach/.nix-profile/bin/nvim" {
+        args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "b.txt"
+    }
+}"#;
+        let expected = r#"layout {
+    tab name="main" {
+        pane command="nvim" {
+            args "a.txt"
+        }
+    }
+}
+layout "alt" {
+    pane command="nvim" {
+        args "b.txt"
+    }
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        let simplified: Vec<&str> = changes
+            .iter()
+            .map(|c| c.simplified_command.as_str())
+            .collect();
+        assert_eq!(simplified, vec!["nvim a.txt", "nvim b.txt"]);
+    }
+
+    #[test]
+    fn test_other_editors_simplified_on_request() {
+        let input = r#"pane command="/usr/bin/vim" {
+    args "-c" "set nu" "My Notes.md"
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        assert_eq!(result, input);
+        assert!(changes.is_empty());
+
+        let options = TransformOptions {
+            simplify_other_editors: true,
+            ..Default::default()
+        };
+        let (result, changes) = process_kdl_content_with(input, &options);
+        assert_eq!(
+            result,
+            r#"pane command="vim" {
+    args "My Notes.md"
+}"#
+        );
+        assert_eq!(changes[0].files, ["My Notes.md"]);
+    }
+
+    #[test]
+    fn test_only_editor_filter() {
+        let input = r#"tab name="edit" {
+    pane command="/home/zach/.nix-profile/bin/nvim" {
+        args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+    }
+    pane command="/usr/bin/vim" {
+        args "-u" "/nix/store/vimrc" "b.txt"
+    }
+}
+tab name="dr project" {
+    pane command="bacon"
+}"#;
+        let expected = r#"tab name="edit" {
+    pane command="/home/zach/.nix-profile/bin/nvim" {
+        args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+    }
+    pane command="vim" {
+        args "b.txt"
+    }
+}
+tab name="dr project" {
+    pane command="bacon"
+}"#;
+        let options = TransformOptions {
+            only_editors: vec!["vim".to_string()],
+            simplify_other_editors: true,
+            ..Default::default()
+        };
+
+        let (result, changes) = process_kdl_content_with(input, &options);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].editor.as_deref(), Some("vim"));
+    }
+
+    #[test]
+    fn test_canonicalize_paths() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::write(project.join("src/main.rs"), "").unwrap();
+        let input = format!(
+            r#"pane command="/home/zach/.nix-profile/bin/nvim" cwd="{}" {{
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "src/main.rs" "missing.rs"
+}}"#,
+            project.display()
+        );
+        let options = TransformOptions {
+            canonicalize_paths: true,
+            ..Default::default()
+        };
+
+        let (_, changes) = process_kdl_content_with(&input, &options);
+
+        let main_rs = project.canonicalize().unwrap().join("src/main.rs");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].simplified_command,
+            format!("nvim {} missing.rs", main_rs.display())
+        );
+
+        // A relative cwd is resolved against the layout's directory
+        let input = r#"pane command="nvim" cwd="project" {
+    args "src/main.rs"
+}"#;
+        let options = TransformOptions {
+            layout_dir: Some(temp.path().to_path_buf()),
+            ..options
+        };
+        let (_, changes) = process_kdl_content_with(input, &options);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].simplified_command,
+            format!("nvim {}", main_rs.display())
+        );
+    }
+
+    #[test]
+    fn test_env_prefixed_nvim() {
+        let input = r#"pane command="env" {
+    args "FOO=bar" "/nix/store/abc/bin/nvim" "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+}
+pane command="/usr/bin/env" {
+    args "FOO=bar" "bacon"
+}"#;
+        let expected = r#"pane command="env" {
+    args "FOO=bar" "nvim" "file.txt"
+}
+pane command="/usr/bin/env" {
+    args "FOO=bar" "bacon"
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "env FOO=bar nvim file.txt");
+        assert_eq!(changes[0].editor.as_deref(), Some("nvim"));
+    }
+
+    #[test]
+    fn test_env_prefix_keeps_nvim_appname() {
+        let input = r#"layout {
+    tab name="notes" {
+        pane command="env" {
+            args "NVIM_APPNAME=work" "NVIM_LOG_FILE=/tmp/nvim.log" "/nix/store/abc/bin/nvim" "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "--cmd" "lua vim.g.loaded_node_provider=0" "file.txt"
+        }
+    }
+    tab name="dr project" {
+        pane command="env" {
+            args "NVIM_APPNAME=work" "/nix/store/abc/bin/nvim" "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+        }
+    }
+}"#;
+        let expected = r#"layout {
+    tab name="notes" {
+        pane command="env" {
+            args "NVIM_APPNAME=work" "NVIM_LOG_FILE=/tmp/nvim.log" "nvim" "file.txt"
+        }
+    }
+    tab name="dr project" {
+        pane command="direnv" {
+            args "exec" "." "env" "NVIM_APPNAME=work" "nvim" "file.txt"
+        }
+    }
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[0].simplified_command,
+            "env NVIM_APPNAME=work NVIM_LOG_FILE=/tmp/nvim.log nvim file.txt"
+        );
+        assert_eq!(
+            changes[1].simplified_command,
+            "direnv exec . env NVIM_APPNAME=work nvim file.txt"
+        );
+        for change in &changes {
+            assert!(!change.simplified_command.contains("--cmd"));
+            assert_eq!(change.editor.as_deref(), Some("nvim"));
+            assert_eq!(change.files, ["file.txt"]);
+        }
+    }
+
+    #[test]
+    fn test_trailing_comment_after_closing_brace() {
+        let input = r#"layout {
+    tab {
+        pane command="/usr/bin/nvim" {
+            args "--cmd" "x=1" "a.txt"
+        } // editor pane
+        pane command="htop" // monitor
+    } // main tab
+}"#;
+        let expected = r#"layout {
+    tab {
+        pane command="nvim" {
+            args "a.txt"
+        } // editor pane
+        pane command="htop" // monitor
+    } // main tab
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+    }
+
+    #[test]
+    fn test_changes_numbered_by_pane() {
+        let input = r#"layout {
+    tab {
+        pane command="/usr/bin/nvim" {
+            args "--cmd" "x=1" "a.txt"
+        }
+        pane split_direction="vertical" {
+            pane
+            pane command="/usr/bin/nvim" {
+                args "--cmd" "x=1" "b.txt"
+            }
+        }
+    }
+}"#;
+
+        let (_, changes) = process_kdl_content(input);
+        let panes: Vec<usize> = changes.iter().map(|change| change.pane).collect();
+        assert_eq!(panes, [1, 4]);
+    }
+
+    #[test]
+    fn test_command_forms() {
+        let input = r#"pane command="/path/nvim.exe" {
+    args "--cmd" "x=1" "a.txt"
+}"#;
+        let cases = [
+            (CommandForm::Bare, "nvim"),
+            (CommandForm::Basename, "nvim.exe"),
+            (CommandForm::KeepPath, "/path/nvim.exe"),
+        ];
+
+        for (command_form, command) in cases {
+            dbg!(command_form);
+            let options = TransformOptions {
+                command_form,
+                ..Default::default()
+            };
+            let (result, changes) = process_kdl_content_with(input, &options);
+            let expected = format!("pane command=\"{}\" {{\n    args \"a.txt\"\n}}", command);
+            assert_eq!(result, expected, "Failed on input: {:?}", command_form);
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].simplified_command, format!("{} a.txt", command));
+
+            let dr_tab = format!("tab name=\"dr x\" {{\n{}\n}}", input);
+            let (result, changes) = process_kdl_content_with(&dr_tab, &options);
+            let expected = format!(
+                "tab name=\"dr x\" {{\npane command=\"direnv\" {{\n    args \"exec\" \".\" \"{}\" \"a.txt\"\n}}\n}}",
+                command
+            );
+            assert_eq!(result, expected, "Failed on input: {:?}", command_form);
+            assert_eq!(changes.len(), 1);
+            assert_eq!(
+                changes[0].simplified_command,
+                format!("direnv exec . {} a.txt", command)
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_extensions() {
+        let input = r#"pane command="/nix/store/abc/bin/nvim" {
+    args "--cmd" "x" "notes" "b.txt" "a.rs"
+}"#;
+        let expected = r#"pane command="nvim" {
+    args "a.rs"
+}"#;
+
+        let options = TransformOptions {
+            file_extensions: vec!["rs".to_string()],
+            ..Default::default()
+        };
+        let (result, changes) = process_kdl_content_with(input, &options);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim a.rs");
+    }
+
+    #[test]
+    fn test_pane_template_simplified() {
+        let input = r#"layout {
+    pane_template name="editor" command="/nix/store/abc/bin/nvim" {
+        args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "notes.md"
+    }
+    tab name="edit" {
+        editor
+    }
+}"#;
+        let expected = r#"layout {
+    pane_template name="editor" command="nvim" {
+        args "notes.md"
+    }
+    tab name="edit" {
+        editor
+    }
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim notes.md");
+    }
+
+    #[test]
+    fn test_non_editor_panes_untouched() {
+        let input = r#"layout {
+    tab name="shells" {
+        pane command="bash" {
+            args "-c" "cat /var/log/syslog | grep nvim; echo done > /tmp/out.txt"
+        }
+        pane command="htop"
+        pane command="/usr/bin/printf" {
+            args "\u{1b}]0;nvim file.txt\u{7}" "--cmd" "a.txt"
+        }
+        pane command="sh" args="-c 'ls | wc -l; nvim --cmd x a.txt'"
+    }
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, input);
+        assert!(changes.is_empty(), "{changes:?}");
+    }
+
+    /// Small deterministic xorshift generator, so failures are reproducible from the seed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    /// Checks that `input` doesn't make processing panic, and that the output parses whenever
+    /// the input did (and is the input itself otherwise).
+    fn assert_round_trips(input: &str) {
+        let (output, _) = process_kdl_content(input);
+        if input.parse::<KdlDocument>().is_ok() {
+            assert!(
+                output.parse::<KdlDocument>().is_ok(),
+                "Output doesn't parse for input: {input:?}\noutput: {output:?}"
+            );
+        } else {
+            assert_eq!(output, input, "Failed on input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_random_input_never_panics() {
+        let fragments = [
+            "layout",
+            "tab",
+            "pane",
+            "pane_template",
+            "{",
+            "}",
+            " ",
+            "\n",
+            ";",
+            "/-",
+            "\"",
+            "\\",
+            "=",
+            "command=",
+            "\"nvim\"",
+            "\"/nix/store/abc/bin/nvim\"",
+            "\"/usr/bin/vim\"",
+            "\"env\"",
+            "\"FOO=bar\"",
+            "\"direnv\"",
+            "args",
+            "args=",
+            "\"--cmd\"",
+            "\"lua x=1\"",
+            "\"a.txt\"",
+            "\"+42\"",
+            "\"\"",
+            "name=\"dr x\"",
+            "cwd=\"src\"",
+            "start_suspended true",
+            "r#\"raw\"#",
+            "\"é\"",
+            "\t",
+            "// comment\n",
+            "/* block */",
+            "-",
+            "1",
+            "true",
+            "null",
+        ];
+        let fixtures = [
+            include_str!("../tests/fixtures/mediactl_input/session-layout.kdl"),
+            include_str!("../tests/fixtures/editors_input/session-layout.kdl"),
+        ];
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for fixture in fixtures {
+            assert_round_trips(fixture);
+        }
+        for _ in 0..500 {
+            // KDL-ish strings built from fragments
+            let len = rng.below(40);
+            let input: String = (0..len).map(|_| rng.pick(&fragments)).collect();
+            assert_round_trips(&input);
+
+            // Fixtures with a random span replaced by a fragment
+            let fixture = fixtures[rng.below(fixtures.len())];
+            let boundaries: Vec<usize> = fixture
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([fixture.len()])
+                .collect();
+            let start = boundaries[rng.below(boundaries.len())];
+            let end = (start + rng.below(64)).min(fixture.len());
+            let end = boundaries.iter().copied().find(|&b| b >= end).unwrap();
+            let input = format!(
+                "{}{}{}",
+                &fixture[..start],
+                rng.pick(&fragments),
+                &fixture[end..]
+            );
+            assert_round_trips(&input);
+        }
+    }
+
+    #[test]
+    fn test_args_as_only_child() {
+        let cases = [
+            (
+                "pane command=\"/nix/store/abc/bin/nvim\" {\n    args \"--cmd\" \"x=1\" \"a.txt\" \"b.txt\"\n}",
+                "pane command=\"nvim\" {\n    args \"a.txt\" \"b.txt\"\n}",
+            ),
+            (
+                "pane command=\"/nix/store/abc/bin/nvim\" { args \"--cmd\" \"x=1\" \"a.txt\"; }",
+                "pane command=\"nvim\" { args \"a.txt\"; }",
+            ),
+            (
+                "tab name=\"edit\" {\n    pane command=\"/nix/store/abc/bin/nvim\" {\n        args \"--cmd\" \"lua vim.g.x=1\" \"src/main.rs\"\n    }\n}",
+                "tab name=\"edit\" {\n    pane command=\"nvim\" {\n        args \"src/main.rs\"\n    }\n}",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content(input);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_compact_single_line_pane() {
+        let options = TransformOptions {
+            compact: true,
+            ..Default::default()
+        };
+        let cases = [
+            (
+                "pane command=\"/nix/store/abc/bin/nvim\" {\n    args \"--cmd\" \"x=1\" \"a.txt\" \"b.txt\"\n}\n",
+                "pane command=\"nvim\" { args \"a.txt\" \"b.txt\"; }\n",
+            ),
+            (
+                "tab name=\"edit\" {\n    pane command=\"/nix/store/abc/bin/nvim\" {\n        args \"--cmd\" \"x=1\" \"a.txt\"\n        start_suspended true\n    }\n}\n",
+                "tab name=\"edit\" {\n    pane command=\"nvim\" { args \"a.txt\"; start_suspended true; }\n}\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+            assert!(
+                result.parse::<KdlDocument>().is_ok(),
+                "Failed on input: {input}"
+            );
+
+            let (again, changes) = process_kdl_content_with(&result, &options);
+            assert_eq!(again, result, "Failed on input: {input}");
+            assert!(changes.is_empty(), "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_shell_exec_nvim_payload() {
+        let cases = [
+            (
+                r#"pane command="/bin/sh" {
+    args "-c" "exec nvim --cmd 'lua vim.opt.packpath:prepend(\"/nix/store/x\")' file.txt"
+}"#,
+                r#"pane command="/bin/sh" {
+    args "-c" "exec nvim file.txt"
+}"#,
+            ),
+            (
+                r#"pane command="bash" {
+    args "-c" "/nix/store/abc/bin/nvim --clean 'my notes.md'"
+}"#,
+                r#"pane command="bash" {
+    args "-c" "nvim 'my notes.md'"
+}"#,
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content(input);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+        }
+
+        // Payloads that don't run nvim, or do more than run it, are left alone
+        for input in [
+            r#"pane command="/bin/sh" { args "-c" "exec htop"; }"#,
+            r#"pane command="/bin/sh" { args "-c" "cd /tmp; exec nvim a.txt" "extra"; }"#,
+        ] {
+            dbg!(input);
+            let (result, changes) = process_kdl_content(input);
+            assert_eq!(result, input, "Failed on input: {input}");
+            assert!(changes.is_empty(), "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_bodyless_joined_command() {
+        let cases = [
+            (
+                "pane command=\"/path/nvim --cmd x file.txt\"\n",
+                "pane command=\"nvim\" {\n    args \"file.txt\"\n}\n",
+            ),
+            (
+                "tab name=\"edit\" {\n    pane command=\"/nix/store/abc/bin/nvim --cmd 'lua vim.g.x = 1' a.txt b.txt\" focus=true\n}\n",
+                "tab name=\"edit\" {\n    pane command=\"nvim\" focus=true {\n        args \"a.txt\" \"b.txt\"\n    }\n}\n",
+            ),
+            (
+                "pane command=\"nvim file.txt\"",
+                "pane command=\"nvim\" {\n    args \"file.txt\"\n}",
+            ),
+            (
+                "pane command=\"nvim -S a.txt\"",
+                "pane command=\"nvim\" {\n    args \"a.txt\"\n}",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content(input);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+            assert!(
+                result.parse::<KdlDocument>().is_ok(),
+                "Failed on input: {input}"
+            );
+        }
+
+        let (_, changes) = process_kdl_content(cases[0].0);
+        assert_eq!(changes[0].original_command, "/path/nvim --cmd x file.txt");
+        assert_eq!(changes[0].simplified_command, "nvim file.txt");
+
+        let (result, changes) = process_kdl_content("pane command=\"nvim --clean\"\n");
+        assert_eq!(result, "pane command=\"nvim\"\n");
+        assert_eq!(changes[0].simplified_command, "nvim");
+
+        // Commands not running nvim, or that already have args, are left alone
+        for input in [
+            "pane command=\"htop --tree\"\n",
+            "pane command=\"nvim --clean\" {\n    args \"a.txt\"\n}\n",
+        ] {
+            dbg!(input);
+            let (result, changes) = process_kdl_content(input);
+            assert_eq!(result, input, "Failed on input: {input}");
+            assert!(changes.is_empty(), "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_dump_ast() {
+        let input = r#"layout {
+    // editor
+    pane command="/nix/store/abc/bin/nvim" focus=true {
+        args "--cmd" "x=1" "a.txt"
+    }
+    pane size=1 command="htop"
+}
+"#;
+        let expected = r#"node layout
+  children
+    node pane
+      prop command = "/nix/store/abc/bin/nvim"
+      prop focus = true
+      children
+        node args
+          arg "--cmd"
+          arg "x=1"
+          arg "a.txt"
+    node pane
+      prop size = 1
+      prop command = "htop"
+"#;
+
+        assert_eq!(dump_ast(input).unwrap(), expected);
+        assert!(dump_ast("pane {").is_err());
+    }
+
+    #[test]
+    fn test_eol_normalization() {
+        let input = "layout {\r\n    pane command=\"/nix/store/abc/bin/nvim\" {\r\n        args \"--cmd\" \"x=1\" \"a.txt\"\r\n    }\r\n}\r\n";
+        let run = |eol| {
+            let options = TransformOptions {
+                eol,
+                ..Default::default()
+            };
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(changes.len(), 1, "Failed on eol: {eol:?}");
+            result
+        };
+
+        let lf = run(Eol::Lf);
+        assert!(!lf.contains('\r'), "{lf:?}");
+        assert_eq!(
+            lf,
+            "layout {\n    pane command=\"nvim\" {\n        args \"a.txt\"\n    }\n}\n"
+        );
+        assert_eq!(run(Eol::Crlf), lf.replace('\n', "\r\n"));
+        assert_eq!(run(Eol::Preserve), lf.replace('\n', "\r\n"));
+
+        // Unchanged layouts are returned as they were
+        let (result, changes) = process_kdl_content_with(
+            "pane command=\"htop\"\r\n",
+            &TransformOptions {
+                eol: Eol::Lf,
+                ..Default::default()
+            },
+        );
+        assert!(changes.is_empty());
+        assert_eq!(result, "pane command=\"htop\"\r\n");
+    }
+
+    #[test]
+    fn test_launcher_prefix() {
+        let options = TransformOptions {
+            launchers: vec!["mise".to_string(), "uv".to_string()],
+            ..Default::default()
+        };
+        let cases = [
+            (
+                r#"pane command="mise" {
+    args "exec" "--" "/path/nvim" "--cmd" "x" "file.txt"
+}"#,
+                r#"pane command="mise" {
+    args "exec" "--" "nvim" "file.txt"
+}"#,
+            ),
+            (
+                r#"pane command="/usr/bin/uv" {
+    args "run" "--with" "pynvim" "/nix/store/abc/bin/nvim" "-u" "init.lua" "main.py"
+}"#,
+                r#"pane command="/usr/bin/uv" {
+    args "run" "--with" "pynvim" "nvim" "main.py"
+}"#,
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+        }
+
+        // Unlisted launchers, and launchers not running nvim, are left alone
+        for (input, options) in [
+            (cases[0].0, TransformOptions::default()),
+            (
+                "pane command=\"mise\" {\n    args \"exec\" \"--\" \"htop\"\n}",
+                options.clone(),
+            ),
+        ] {
+            dbg!(input);
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(result, input, "Failed on input: {input}");
+            assert!(changes.is_empty(), "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_pane_attributes_preserved() {
+        let cases = [
+            (
+                r#"pane name="edit" command="/path/nvim" focus=true size="50%" split_direction="vertical" {
+    args "--cmd" "x=1" "a.txt"
+}"#,
+                r#"pane name="edit" command="nvim" focus=true size="50%" split_direction="vertical" {
+    args "a.txt"
+}"#,
+            ),
+            (
+                r#"pane command="/usr/bin/vim" name="notes" focus=true {
+    args "-u" "/nix/store/abc/vimrc" "notes.txt"
+}"#,
+                r#"pane command="vim" name="notes" focus=true {
+    args "notes.txt"
+}"#,
+            ),
+            (
+                r#"tab name="dr project" {
+    pane size=1 name="edit" command="/path/nvim" focus=true {
+        args "--cmd" "x=1" "a.txt"
+    }
+}"#,
+                r#"tab name="dr project" {
+    pane size=1 name="edit" command="direnv" focus=true {
+        args "exec" "." "nvim" "a.txt"
+    }
+}"#,
+            ),
+        ];
+
+        let options = TransformOptions {
+            simplify_other_editors: true,
+            ..Default::default()
+        };
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_convert_to_edit_when_single() {
+        let options = TransformOptions {
+            convert_to_edit_when_single: true,
+            ..Default::default()
+        };
+        let cases = [
+            // A clean single-file pane is converted
+            (
+                "layout {\n    pane command=\"/path/nvim\" {\n        args \"--cmd\" \"x=1\" \"a.txt\"\n    }\n}\n",
+                "layout {\n    pane edit=\"a.txt\"\n}\n",
+            ),
+            // start_suspended, extra attributes, several files and + commands keep the args form
+            (
+                "pane command=\"/path/nvim\" {\n    args \"--cmd\" \"x=1\" \"a.txt\"\n    start_suspended true\n}\n",
+                "pane command=\"nvim\" {\n    args \"a.txt\"\n    start_suspended true\n}\n",
+            ),
+            (
+                "pane command=\"/path/nvim\" focus=true {\n    args \"--cmd\" \"x=1\" \"a.txt\"\n}\n",
+                "pane command=\"nvim\" focus=true {\n    args \"a.txt\"\n}\n",
+            ),
+            (
+                "pane command=\"/path/nvim\" {\n    args \"--cmd\" \"x=1\" \"a.txt\" \"b.txt\"\n}\n",
+                "pane command=\"nvim\" {\n    args \"a.txt\" \"b.txt\"\n}\n",
+            ),
+            (
+                "pane command=\"/path/nvim\" {\n    args \"--cmd\" \"x=1\" \"+42\"\n}\n",
+                "pane command=\"nvim\" {\n    args \"+42\"\n}\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes.len(), 1, "Failed on input: {input}");
+            assert!(
+                result.parse::<KdlDocument>().is_ok(),
+                "Failed on input: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_and_whitespace_content() {
+        let cases = ["", "\n", "  \n\t\n\r\n   "];
+
+        for input in cases.iter() {
+            dbg!(input);
+            let (result, changes) = process_kdl_content(input);
+            assert_eq!(result, *input, "Failed on input: {input:?}");
+            assert!(changes.is_empty(), "Failed on input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_unknown_nodes_preserved_verbatim() {
+        let input = r#"layout {
+    cwd "/home/zach/Dev"
+    default_tab_template {
+        pane size=1   borderless=true {
+            plugin location="zellij:tab-bar"
+        }
+        children
+        pane size=2 borderless=true {
+            plugin location="zellij:status-bar"   // trailing comment
+        }
+    }
+    swap_tiled_layout name="vertical" {
+        tab max_panes=5 {
+            pane split_direction="vertical" { pane; pane { children; }; }
+        }
+    }
+    tab name="edit" {
+        pane command="/home/zach/.nix-profile/bin/nvim" {
+            args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+        }
+    }
+}
+"#;
+        let expected = input.replace(
+            r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+            args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt""#,
+            r#"pane command="nvim" {
+            args "a.txt""#,
+        );
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_escaped_newline_in_arg() {
+        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+    args "--cmd" "lua vim.g.banner = 'one\ntwo'\nvim.opt.packpath:prepend('/nix/store/test')" "a.txt" "b.txt"
+}"#;
+        let expected = r#"pane command="nvim" {
+    args "a.txt" "b.txt"
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim a.txt b.txt");
+    }
+
+    #[test]
+    fn test_slashdash_pane_ignored() {
+        let input = r#"tab name="edit" {
+    /-pane command="/home/zach/.nix-profile/bin/nvim" {
+        args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+    }
+}
+/-tab name="dr project" {
+    pane command="bacon"
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, input);
+        assert!(changes.is_empty());
+
+        // A commented-out args node in a live pane is neither read nor rewritten
+        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+    /-args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "old.txt"
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+}"#;
+        let expected = r#"pane command="nvim" {
+    /-args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "old.txt"
+    args "a.txt"
+}"#;
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim a.txt");
+    }
+
+    #[test]
+    fn test_attribute_order_preserved() {
+        let input = r#"pane size="50%" command="/home/zach/.nix-profile/bin/nvim" name="editor" focus=true {
+    start_suspended true
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+    cwd "/home/zach/project"
+}"#;
+        let expected = r#"pane size="50%" command="nvim" name="editor" focus=true {
+    start_suspended true
+    args "file.txt"
+    cwd "/home/zach/project"
+}"#;
+
+        let (result, changes) = process_kdl_content(input);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+    }
+
+    #[test]
+    fn test_custom_args_node_name() {
+        let input = r#"pane command="/home/zach/.nix-profile/bin/nvim" {
+    files "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+    start_suspended true
+}"#;
+        let expected = r#"pane command="nvim" {
+    files "file.txt"
+    start_suspended true
+}"#;
+
+        let options = TransformOptions {
+            args_node: "files".to_string(),
+            ..Default::default()
+        };
+        let (result, changes) = process_kdl_content_with(input, &options);
+        dbg!(&result);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim file.txt");
+    }
+
+    #[test]
+    fn test_args_node_ignore_case() {
+        let input = r#"pane command="/nix/store/abc/bin/nvim" {
+    Args "--cmd" "lua vim.g.x=1" "file.txt"
+}"#;
+        let expected = r#"pane command="nvim" {
+    Args "file.txt"
+}"#;
+
+        let options = TransformOptions {
+            args_node_ignore_case: true,
+            ..Default::default()
+        };
+        let (result, changes) = process_kdl_content_with(input, &options);
+        assert_eq!(result, expected);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].files, ["file.txt"]);
+
+        let (result, changes) = process_kdl_content(input);
+        assert!(result.contains(r#"Args "--cmd" "lua vim.g.x=1" "file.txt""#));
+        assert!(changes.iter().all(|change| change.files.is_empty()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_transform_cmd() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let input = r#"pane command="/nix/store/abc/bin/nvim" {
+    args "--cmd" "lua vim.g.x=1" "file.txt"
+}"#;
+        let transform = |program: PathBuf| {
+            let options = TransformOptions {
+                transform_cmd: Some(program),
+                ..Default::default()
+            };
+            process_kdl_content_with(input, &options)
+        };
+
+        let (result, changes) = transform(PathBuf::from("cat"));
+        assert_eq!(result, input);
+        assert!(changes.is_empty());
+
+        let temp = tempfile::tempdir().unwrap();
+        let script = temp.path().join("strip-store");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nsed -e 's|/nix/store/abc/bin/||' -e \"s|--cmd 'lua vim.g.x=1' ||\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let (result, changes) = transform(script);
+        assert_eq!(
+            result,
+            r#"pane command="nvim" {
+    args "file.txt"
+}"#
+        );
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].simplified_command, "nvim file.txt");
+        assert_eq!(changes[0].files, ["file.txt"]);
+
+        let (result, changes) = transform(PathBuf::from("false"));
+        assert_eq!(result, input);
+        assert!(changes.is_empty());
+
+        let cases = [
+            (
+                r#"pane command="env" {
+    args "A=1" "/nix/store/abc/bin/nvim" "--cmd" "lua vim.g.x=1" "file.txt"
+}"#,
+                r#"pane command="env" {
+    args "A=1" "nvim" "other.txt"
+}"#,
+            ),
+            (
+                r#"pane command="sh" {
+    args "-c" "exec /nix/store/abc/bin/nvim --cmd 'lua vim.g.x=1' file.txt"
+}"#,
+                r#"pane command="sh" {
+    args "-c" "exec nvim other.txt"
+}"#,
+            ),
+            (
+                r#"pane command="/nix/store/abc/bin/nvim --cmd 'lua vim.g.x=1' file.txt""#,
+                r#"pane command="nvim" {
+    args "other.txt"
+}"#,
+            ),
+            (
+                r#"tab name="dr x" {
+    pane command="/nix/store/abc/bin/nvim" {
+        args "--cmd" "lua vim.g.x=1" "file.txt"
+    }
+}"#,
+                r#"tab name="dr x" {
+    pane command="direnv" {
+        args "exec" "." "nvim" "other.txt"
+    }
+}"#,
+            ),
+            (
+                r#"tab name="dr x" {
+    pane command="env" {
+        args "A=1" "/nix/store/abc/bin/nvim" "--cmd" "lua vim.g.x=1" "file.txt"
+    }
+}"#,
+                r#"tab name="dr x" {
+    pane command="direnv" {
+        args "exec" "." "env" "A=1" "nvim" "other.txt"
+    }
+}"#,
+            ),
+        ];
+        let rename = temp.path().join("rename");
+        std::fs::write(&rename, "#!/bin/sh\nsed 's|.*|nvim other.txt|'\n").unwrap();
+        std::fs::set_permissions(&rename, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let options = TransformOptions {
+            transform_cmd: Some(rename),
+            ..Default::default()
+        };
+        for (input, expected) in cases {
+            dbg!(input);
+            let (result, changes) = process_kdl_content_with(input, &options);
+            assert_eq!(result, expected, "Failed on input: {input}");
+            assert_eq!(changes[0].files, ["other.txt"], "Failed on input: {input}");
+        }
+    }
+
     #[test]
     fn test_tab_dr_direnv_single_pane() {
         let input = r#"
@@ -0,0 +1,13 @@
+pub mod config;
+pub mod diagnostic;
+pub(crate) mod diff;
+pub mod editor;
+pub mod error;
+pub(crate) mod json;
+pub mod kdl;
+pub mod nvim;
+pub mod scan;
+pub mod serve;
+pub mod utils;
+pub mod walker;
+pub(crate) mod zip;
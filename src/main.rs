@@ -1,33 +1,376 @@
 mod cli;
-mod kdl;
-mod nvim;
-mod utils;
 
 use clap::Parser;
-use cli::{Cli, Commands};
-use kdl::scan_layouts;
-use nvim::format_nvim;
-use utils::{expand_home, log_command};
+use cli::{Cli, Commands, ExitCodes};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zellij_command_hook::config::{Config, EDITORS_ENV, resolve_editors};
+use zellij_command_hook::diagnostic::Diagnostics;
+use zellij_command_hook::editor::{default_editors, detect};
+use zellij_command_hook::error::ScanError;
+use zellij_command_hook::kdl::{TransformOptions, dump_ast};
+use zellij_command_hook::nvim::{DEFAULT_MAX_COMMAND_LEN, format_nvim_with_limit};
+use zellij_command_hook::scan::{
+    InspectionReport, OutputFormat, ScanOptions, changed_files, clean_artifacts,
+    find_empty_sessions, find_redundant_panes, find_unrecognized_panes, process_stdin,
+    resolve_session_layout, scan_layouts, scan_zip, verify_content,
+};
+use zellij_command_hook::serve::serve;
+use zellij_command_hook::utils::{
+    LOCK_FILE, LOG_PATH, RunLock, expand_home, log_command, log_warning, migrate_log,
+    zellij_cache_dir,
+};
+
+/// Exit code for a successful run that changed files, with `--exit-codes extended`.
+const EXIT_CHANGES_APPLIED: i32 = 10;
 
 fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Some(Commands::ScanLayouts {
-            path,
-            dry_run,
-            quiet,
+        Some(Commands::ScanLayouts(args)) => {
+            let config = load_config(&cli);
+            let editors = recognized_editors(&config, &args.editors);
+            let diagnostics = match args.format {
+                OutputFormat::Json => Diagnostics::Json,
+                OutputFormat::Human | OutputFormat::Csv => Diagnostics::Text,
+            };
+            let transform = TransformOptions {
+                resolve_shebang: args.resolve_shebang,
+                args_node: args.args_node.clone(),
+                args_node_ignore_case: args.args_node_ignore_case,
+                ignore_case: args.ignore_case || cfg!(windows),
+                editors,
+                simplify_other_editors: args.simplify_other_editors,
+                inline_args_to_child: args.inline_args_to_child,
+                indent: args.indent.or(config.indent),
+                only_editors: args.only_editors.clone(),
+                canonicalize_paths: args.canonicalize_paths,
+                clear_suspended: args.clear_suspended,
+                verify_editor_exists: args.verify_editor_exists,
+                layout_dir: None,
+                command_form: args.command_form,
+                file_extensions: args
+                    .file_extensions
+                    .iter()
+                    .map(|ext| ext.trim_start_matches('.').to_string())
+                    .collect(),
+                compact: args.compact,
+                diagnostics,
+                eol: args.eol,
+                launchers: args.launchers.clone(),
+                convert_to_edit_when_single: args.convert_to_edit_when_single,
+                transform_cmd: args.transform_cmd.clone(),
+            };
+            if args.stdin {
+                if let Err(e) = process_stdin(cli.verbose, args.dry_run, &transform) {
+                    diagnostics.error(&e.to_string(), e.path());
+                    std::process::exit(1);
+                }
+                return;
+            }
+            let cache_dir = zellij_cache_dir();
+            let layouts: Vec<PathBuf> = args
+                .sessions
+                .iter()
+                .map(|name| {
+                    resolve_session_layout(&cache_dir, name).unwrap_or_else(|e| {
+                        diagnostics.error(&e.to_string(), e.path());
+                        std::process::exit(1);
+                    })
+                })
+                .collect();
+            let options = ScanOptions {
+                verbose: cli.verbose,
+                dry_run: args.dry_run,
+                quiet: args.quiet || args.only_changed || args.format != OutputFormat::Human,
+                interactive: args.interactive,
+                force: args.force,
+                lossy: args.lossy,
+                no_validate: args.no_validate,
+                summary_json_file: args.summary_json_file.clone(),
+                patch_file: args.patch.clone(),
+                output_dir: args.output_dir.clone(),
+                on_conflict: args.on_conflict,
+                no_recurse: args.no_recurse,
+                layouts: layouts.clone(),
+                by_content: args.by_content,
+                max_files: args.max_files,
+                on_max_files: args.on_max_files,
+                group_by_session: args.group_by_session,
+                tree: args.tree,
+                report_largest: args.report_largest,
+                preview_count: args.preview_count,
+                report_by_extension: args.report_by_extension,
+                profile: args.profile,
+                editor_aliases: config
+                    .editor_aliases
+                    .clone()
+                    .into_iter()
+                    .chain(args.editor_aliases.iter().cloned())
+                    .collect(),
+                format: args.format,
+                write_delay: Duration::from_millis(args.write_delay),
+                file_timeout: args.file_timeout.map(Duration::from_millis),
+                read_batch: args.read_batch,
+                sidecar_report: args.sidecar_report,
+                keep_backups: args.keep_backups,
+                log_file: cli.log_file.clone(),
+                transform,
+            };
+            let expanded_path = || {
+                if layouts.is_empty() {
+                    return expand_home(&args.path);
+                }
+                common_dir(&layouts).display().to_string()
+            };
+            if args.report_redundant_panes {
+                let report = find_redundant_panes(&expanded_path(), &options);
+                print_inspection(report, &options, "No redundant nvim panes found");
+                return;
+            }
+            if args.report_unrecognized {
+                let report = find_unrecognized_panes(&expanded_path(), &options);
+                print_inspection(report, &options, "No unrecognized editor panes found");
+                return;
+            }
+            if args.report_empty_sessions {
+                let report = find_empty_sessions(&expanded_path(), &options);
+                print_inspection(report, &options, "No empty sessions found");
+                return;
+            }
+            // Runs that don't write need no lock
+            let _lock = if args.no_lock || args.dry_run {
+                None
+            } else {
+                let log_file = cli.log_file.as_deref().unwrap_or(Path::new(LOG_PATH));
+                let lock_path = log_file.with_file_name(LOCK_FILE);
+                match RunLock::acquire(&lock_path) {
+                    Ok(lock) => Some(lock),
+                    Err(e) => {
+                        diagnostics.error(&e.to_string(), None);
+                        std::process::exit(1);
+                    }
+                }
+            };
+            let result = match &args.from_zip {
+                Some(zip_path) => scan_zip(zip_path, args.rewrite_zip && !args.dry_run, &options),
+                None => scan_layouts(&expanded_path(), &options),
+            };
+            let wrote = !args.dry_run
+                && args.patch.is_none()
+                && (args.from_zip.is_none() || args.rewrite_zip);
+            match result {
+                Ok(outcome) => {
+                    for e in &outcome.errors {
+                        diagnostics.scan_error(e);
+                    }
+                    if args.only_changed && wrote {
+                        for path in changed_files(&outcome) {
+                            println!("{}", path);
+                        }
+                    }
+                    if args.detect && !outcome.changes.is_empty() {
+                        std::process::exit(1);
+                    }
+                    if args.exit_codes == ExitCodes::Extended {
+                        if !outcome.errors.is_empty() {
+                            std::process::exit(1);
+                        }
+                        if wrote && !outcome.changes.is_empty() {
+                            std::process::exit(EXIT_CHANGES_APPLIED);
+                        }
+                    }
+                }
+                Err(e) => {
+                    diagnostics.error(&e.to_string(), e.path());
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Serve) => {
+            let transform = TransformOptions {
+                editors: recognized_editors(&load_config(&cli), &[]),
+                ..Default::default()
+            };
+            let stdin = std::io::stdin();
+            if let Err(e) = serve(stdin.lock(), std::io::stdout(), &transform) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::MigrateLog { path }) => {
+            let path = path
+                .as_deref()
+                .or(cli.log_file.as_deref())
+                .unwrap_or(Path::new(LOG_PATH));
+            let migrated = std::fs::read_to_string(path)
+                .map(|content| migrate_log(&content, |w| eprintln!("Warning: {}", w)))
+                .and_then(|migrated| std::fs::write(path, migrated));
+            if let Err(e) = migrated {
+                eprintln!("Error: migrating {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Clean { path, dry_run }) => {
+            let outcome = match clean_artifacts(Path::new(&expand_home(path)), *dry_run) {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let verb = if *dry_run { "Would remove" } else { "Removed" };
+            for path in &outcome.removed {
+                println!("{}: {}", verb, path.display());
+            }
+            println!("{} {} file(s)", verb, outcome.removed.len());
+            for e in &outcome.errors {
+                eprintln!("Error {}", e);
+            }
+            if !outcome.errors.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::DumpAst { file }) => {
+            let dumped = match std::fs::read_to_string(file) {
+                Ok(content) => {
+                    dump_ast(&content).map_err(|e| format!("parsing {}: {}", file.display(), e))
+                }
+                Err(e) => Err(format!("reading {}: {}", file.display(), e)),
+            };
+            match dumped {
+                Ok(tree) => print!("{}", tree),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Verify { file, expected }) => {
+            let read = |path: &Path| {
+                std::fs::read_to_string(path)
+                    .map_err(|e| format!("reading {}: {}", path.display(), e))
+            };
+            let (content, expected_content) =
+                match read(file).and_then(|c| Ok((c, read(expected)?))) {
+                    Ok(read) => read,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+            let config = load_config(&cli);
+            let transform = TransformOptions {
+                editors: recognized_editors(&config, &[]),
+                indent: config.indent,
+                ..Default::default()
+            };
+            let path = file.display().to_string();
+            match verify_content(&path, &content, &expected_content, &transform) {
+                Ok(None) => {}
+                Ok(Some(diff)) => {
+                    print!("{}", diff);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Error: parsing {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Detect {
+            command,
+            simplify_other_editors,
+            ignore_case,
         }) => {
-            let expanded_path = expand_home(path);
-            scan_layouts(&expanded_path, cli.verbose, *dry_run, *quiet);
+            let editors = recognized_editors(&load_config(&cli), &[]);
+            let ignore_case = *ignore_case || cfg!(windows);
+            println!(
+                "{}",
+                detect(command, &editors, ignore_case, *simplify_other_editors)
+            );
         }
         None => {
             // Original behavior
             let command = std::env::var("RESURRECT_COMMAND").expect("RESURRECT_COMMAND not set");
-            let formatted = format_nvim(&command);
+            let max_len = load_config(&cli)
+                .max_command_len
+                .unwrap_or(DEFAULT_MAX_COMMAND_LEN);
+            let log_file = cli.log_file.as_deref().unwrap_or(Path::new(LOG_PATH));
+            let formatted = format_nvim_with_limit(&command, max_len, |warning| {
+                eprintln!("Warning: {}", warning);
+                if !cli.print_command_only {
+                    log_warning(log_file, warning);
+                }
+            });
             println!("{formatted}");
 
-            log_command(&command, &formatted);
+            if !cli.print_command_only
+                && let Err(e) = log_command(log_file, &command, &formatted)
+            {
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
+}
+
+/// Prints what a read-only `--report-*` mode found, one per line. Exits if any file couldn't
+/// be inspected.
+fn print_inspection<T: Display>(
+    report: Result<InspectionReport<T>, ScanError>,
+    options: &ScanOptions,
+    none_found: &str,
+) {
+    let diagnostics = options.transform.diagnostics;
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            diagnostics.error(&e.to_string(), e.path());
+            std::process::exit(1);
         }
+    };
+    for e in &report.errors {
+        diagnostics.scan_error(e);
     }
+    for found in &report.found {
+        println!("{}", found);
+    }
+    if report.found.is_empty() && !options.quiet {
+        println!("{}", none_found);
+    }
+    if !report.errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Loads the config file given by `--config` or the default one. Exits if it can't be loaded.
+fn load_config(cli: &Cli) -> Config {
+    match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Resolves the editors to simplify from `--editor` flags, the config file and the
+/// environment.
+fn recognized_editors(config: &Config, flags: &[String]) -> Vec<String> {
+    resolve_editors(
+        flags,
+        config,
+        std::env::var(EDITORS_ENV).ok().as_deref(),
+        default_editors(),
+    )
+}
+
+/// The deepest directory containing all of `paths`, so `--session` only walks the part of the
+/// cache dir holding the resolved layouts.
+fn common_dir(paths: &[PathBuf]) -> PathBuf {
+    let mut dir = paths[0].parent().unwrap_or(Path::new("")).to_path_buf();
+    while !paths.iter().all(|path| path.starts_with(&dir)) && dir.pop() {}
+    dir
 }
@@ -1,43 +1,140 @@
+use std::path::Path;
+
+/// Commands longer than this many bytes are left alone by [`format_nvim`].
+pub const DEFAULT_MAX_COMMAND_LEN: usize = 1024 * 1024;
+
 /// Formats a long nvim command into a simple "nvim filename" format.
 /// Extracts filenames from the end of the command, ignoring flags and options.
 pub fn format_nvim(command: &str) -> String {
-    let parts: Vec<&str> = command.split(' ').collect();
-    if parts.is_empty() {
+    format_nvim_with_limit(command, DEFAULT_MAX_COMMAND_LEN, |warning| {
+        eprintln!("Warning: {}", warning)
+    })
+}
+
+/// [`format_nvim`] with a custom length limit. Commands longer than `max_len` bytes are
+/// returned unchanged without being split, and `warn` is called with the reason.
+pub fn format_nvim_with_limit(command: &str, max_len: usize, warn: impl FnOnce(&str)) -> String {
+    if command.len() > max_len {
+        warn(&format!(
+            "command is {} bytes, over the {} byte limit; leaving it unchanged",
+            command.len(),
+            max_len
+        ));
         return command.to_string();
     }
-    let first = parts[0];
-    if !first.ends_with("nvim") && !first.ends_with("nvim.exe") {
-        return command.to_string();
+
+    let parts: Vec<&str> = command.split(' ').collect();
+    match nvim_file_args(&parts, cfg!(windows)) {
+        Some(files) => format!("nvim {}", files.join(" ")),
+        None => command.to_string(),
+    }
+}
+
+/// vim and nvim flags that take the next arg as their value. Shared by [`nvim_file_args`] and
+/// the vim and nvim [`EditorRule`](crate::editor::EditorRule)s. `-S` isn't one: its session
+/// file is optional, so `nvim -S notes.md` opens notes.md.
+pub(crate) const VALUE_FLAGS: &[&str] = &[
+    "--cmd",
+    "-c",
+    "-u",
+    "-U",
+    "-i",
+    "-s",
+    "-w",
+    "-W",
+    "-t",
+    "--listen",
+    "--startuptime",
+];
+
+/// Token-level core of [`format_nvim`]: given the program and its args, returns the args to
+/// keep, or `None` if the program isn't nvim. These are the trailing filenames, preceded by a
+/// `+<line>` or `+<command>` token if one comes right before them. Filenames are trimmed, and
+/// ones left empty are dropped.
+pub fn nvim_file_args<'a>(tokens: &[&'a str], ignore_case: bool) -> Option<Vec<&'a str>> {
+    nvim_file_args_matching(tokens, ignore_case, could_be_filename)
+}
+
+/// [`nvim_file_args`] with a custom test for which trailing args are filenames, such as
+/// [`has_extension`]. The scan stops at the first arg that fails it.
+pub fn nvim_file_args_matching<'a>(
+    tokens: &[&'a str],
+    ignore_case: bool,
+    is_file: impl Fn(&str) -> bool,
+) -> Option<Vec<&'a str>> {
+    let (first, args) = tokens.split_first()?;
+    if !is_nvim(first, ignore_case) {
+        return None;
     }
 
     let mut file_names = Vec::new();
-    for part in parts.iter().rev() {
+    for (idx, part) in args.iter().enumerate().rev() {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
         if part.starts_with('-') {
             break;
         }
-        if part.ends_with("nvim") {
+        if idx > 0 && VALUE_FLAGS.contains(&args[idx - 1].trim()) {
+            // The value of a flag like `--cmd x`, not a file
+            break;
+        }
+        if part.starts_with('+') {
+            // Startup command for the files, e.g. `+42` or `+startinsert`
+            file_names.push(part);
             break;
         }
-        if could_be_filename(part) {
-            file_names.push(*part);
+        if is_file(part) {
+            file_names.push(part);
         } else {
             break;
         }
     }
 
-    let files = file_names
-        .iter()
-        .rev()
-        .cloned()
-        .collect::<Vec<&str>>()
-        .join(" ");
+    file_names.reverse();
+    Some(file_names)
+}
+
+/// Checks if a command token (possibly a full path) is nvim, by its basename without an
+/// [`EXECUTABLE_EXTENSIONS`] extension. With `ignore_case`, `NVIM.EXE` and `Nvim` match too.
+pub fn is_nvim(token: &str, ignore_case: bool) -> bool {
+    let stem = program_stem(token, EXECUTABLE_EXTENSIONS, ignore_case);
+    if ignore_case {
+        stem.eq_ignore_ascii_case("nvim")
+    } else {
+        stem == "nvim"
+    }
+}
+
+/// Extensions stripped from a program's basename before comparing it to an editor name.
+pub const EXECUTABLE_EXTENSIONS: &[&str] = if cfg!(windows) {
+    &[".exe", ".bat", ".cmd"]
+} else {
+    &[".exe"]
+};
 
-    format!("nvim {}", files)
+/// The basename of `program`, without its directory or one of `extensions`.
+pub fn program_stem<'a>(program: &'a str, extensions: &[&str], ignore_case: bool) -> &'a str {
+    let basename = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    for ext in extensions {
+        let Some(split) = basename.len().checked_sub(ext.len()) else {
+            continue;
+        };
+        if split == 0 || !basename.is_char_boundary(split) {
+            continue;
+        }
+        let suffix = &basename[split..];
+        if suffix == *ext || (ignore_case && suffix.eq_ignore_ascii_case(ext)) {
+            return &basename[..split];
+        }
+    }
+    basename
 }
 
 /// Checks if a string could be a valid filename.
 /// Returns false for forbidden characters that aren't allowed in POSIX filenames.
-fn could_be_filename(s: &str) -> bool {
+pub(crate) fn could_be_filename(s: &str) -> bool {
     if s.as_bytes().contains(&0) {
         return false;
     }
@@ -50,6 +147,16 @@ fn could_be_filename(s: &str) -> bool {
     true
 }
 
+/// Checks if `name` ends in one of `extensions`, given without the leading dot.
+pub fn has_extension(name: &str, extensions: &[String], ignore_case: bool) -> bool {
+    let Some(extension) = Path::new(name).extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    extensions.iter().any(|allowed| {
+        allowed == extension || (ignore_case && allowed.eq_ignore_ascii_case(extension))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,6 +197,12 @@ mod tests {
                 "nvim asdf3 asdf4",
             ),
             ("nvim asdf", "nvim asdf"),
+            ("nvim +startinsert file.txt", "nvim +startinsert file.txt"),
+            ("nvim --cmd x=1 +42 file.txt", "nvim +42 file.txt"),
+            ("nvim +startinsert -d a b", "nvim a b"),
+            // A flag's value isn't a file, even if it looks like one
+            ("nvim --cmd x file.txt", "nvim file.txt"),
+            ("nvim -u init.lua notes.md", "nvim notes.md"),
         ];
 
         for (input, expected) in cases.iter() {
@@ -97,4 +210,179 @@ mod tests {
             assert_eq!(format_nvim(input), *expected, "Failed on input: {input}");
         }
     }
+    #[test]
+    fn test_format_nvim_directories() {
+        let cases = [
+            ("nvim .", "nvim ."),
+            ("nvim src/", "nvim src/"),
+            ("nvim /some/dir/", "nvim /some/dir/"),
+            ("nvim a.txt src/ b.txt", "nvim a.txt src/ b.txt"),
+            ("/nix/store/abc/bin/nvim --cmd x=1 src/ .", "nvim src/ ."),
+            // A directory named like the editor is still a directory
+            (
+                "nvim /home/zach/.config/nvim",
+                "nvim /home/zach/.config/nvim",
+            ),
+        ];
+
+        for (input, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(format_nvim(input), *expected, "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_format_nvim_over_length_limit() {
+        let command = format!(
+            "/nix/store/abc/bin/nvim --cmd \"lua {}\" file.txt",
+            "x".repeat(2 * 1024 * 1024)
+        );
+        let mut warnings = Vec::new();
+        let result = format_nvim_with_limit(&command, DEFAULT_MAX_COMMAND_LEN, |warning| {
+            warnings.push(warning.to_string())
+        });
+        assert_eq!(result, command);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("over the 1048576 byte limit"));
+
+        let result = format_nvim_with_limit(&command, usize::MAX, |_| panic!("unexpected warning"));
+        assert_eq!(result, "nvim file.txt");
+    }
+
+    #[test]
+    fn test_nvim_file_args_plus_command() {
+        let cases: [(&[&str], Option<&[&str]>); 4] = [
+            (
+                &["nvim", "+startinsert", "file.txt"],
+                Some(&["+startinsert", "file.txt"]),
+            ),
+            (
+                &["nvim", "+normal! Gg", "file.txt"],
+                Some(&["+normal! Gg", "file.txt"]),
+            ),
+            (
+                &["nvim", "+startinsert", "--cmd", "set ft=lua", "file.txt"],
+                Some(&["file.txt"]),
+            ),
+            (&["vim", "+startinsert", "file.txt"], None),
+        ];
+
+        for (input, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                nvim_file_args(input, false).as_deref(),
+                *expected,
+                "Failed on input: {input:?}"
+            );
+        }
+    }
+    #[test]
+    fn test_nvim_file_args_drops_blank_filenames() {
+        let cases: [(&[&str], &[&str]); 3] = [
+            (&["nvim", "--cmd", "x=1", "a.txt", ""], &["a.txt"]),
+            (
+                &["nvim", "--cmd", "x=1", " a.txt", "  ", "b.txt "],
+                &["a.txt", "b.txt"],
+            ),
+            (&["nvim", "--cmd", "x=1", ""], &[]),
+        ];
+
+        for (input, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                nvim_file_args(input, false).as_deref(),
+                Some(*expected),
+                "Failed on input: {input:?}"
+            );
+        }
+        assert_eq!(
+            format_nvim("nvim --cmd x=1 a.txt  b.txt "),
+            "nvim a.txt b.txt"
+        );
+    }
+
+    #[test]
+    fn test_nvim_file_args_with_extensions() {
+        let extensions = ["rs".to_string()];
+        let is_file = |arg: &str| has_extension(arg, &extensions, false);
+        let cases: [(&[&str], &[&str]); 4] = [
+            (&["nvim", "--cmd", "x", "a.rs", "notarg"], &[]),
+            (&["nvim", "--cmd", "x", "notarg", "a.rs"], &["a.rs"]),
+            (
+                &["nvim", "b.txt", "a.rs", "src/lib.rs"],
+                &["a.rs", "src/lib.rs"],
+            ),
+            (&["nvim", "+42", "a.rs"], &["+42", "a.rs"]),
+        ];
+
+        for (input, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                nvim_file_args_matching(input, false, is_file).as_deref(),
+                Some(*expected),
+                "Failed on input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_nvim_ignore_case() {
+        let cases = [
+            ("C:\\tools\\NVIM.EXE", true, true),
+            ("C:\\tools\\NVIM.EXE", false, false),
+            ("Nvim", true, true),
+            ("Nvim", false, false),
+            ("/usr/bin/nvim", false, true),
+            ("nvim.exe", false, true),
+            ("/usr/bin/vim", true, false),
+        ];
+
+        for (input, ignore_case, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                is_nvim(input, *ignore_case),
+                *expected,
+                "Failed on input: {input} (ignore_case: {ignore_case})"
+            );
+        }
+        assert_eq!(
+            nvim_file_args(&["C:\\tools\\NVIM.EXE", "--cmd", "x=1", "file.txt"], true),
+            Some(vec!["file.txt"])
+        );
+    }
+
+    #[test]
+    fn test_program_stem() {
+        let extensions = [".exe", ".bat", ".cmd"];
+        let cases = [
+            ("C:\\tools\\nvim.bat", false, "nvim"),
+            ("C:\\tools\\NVIM.CMD", true, "NVIM"),
+            ("C:\\tools\\NVIM.CMD", false, "NVIM.CMD"),
+            ("/usr/bin/nvim", false, "nvim"),
+            ("nvim.sh", false, "nvim.sh"),
+            (".bat", false, ".bat"),
+        ];
+
+        for (input, ignore_case, expected) in cases.iter() {
+            dbg!(input);
+            assert_eq!(
+                program_stem(input, &extensions, *ignore_case),
+                *expected,
+                "Failed on input: {input} (ignore_case: {ignore_case})"
+            );
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_is_nvim_windows_wrappers() {
+        for input in ["C:\\tools\\nvim.bat", "C:\\tools\\nvim.cmd", "NVIM.BAT"] {
+            dbg!(input);
+            assert!(is_nvim(input, true), "Failed on input: {input}");
+        }
+        assert_eq!(
+            format_nvim("C:\\tools\\nvim.cmd --cmd x=1 file.txt"),
+            "nvim file.txt"
+        );
+    }
 }
@@ -0,0 +1,2965 @@
+use crate::diff::unified_diff;
+use crate::error::ScanError;
+use crate::json::Json;
+use crate::kdl::{
+    Changes, TransformOptions, editor_selected, process_kdl_content_with, try_process_kdl_content,
+};
+use crate::utils::{LOG_PATH, Logger, processed_log_entry};
+use crate::walker::{
+    PaneWalker, RedundantPanes, UnrecognizedPane, has_panes, redundant_panes, unrecognized_panes,
+};
+use crate::zip::{read_archive, write_archive};
+use chrono::Local;
+use kdl::{KdlDocument, KdlError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Options controlling a scan.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Print per-file progress and detailed changes.
+    pub verbose: bool,
+    /// Compute changes without writing anything.
+    pub dry_run: bool,
+    /// Don't print anything.
+    pub quiet: bool,
+    /// Show each file's diff and ask on stdin before writing it.
+    pub interactive: bool,
+    /// Overwrite files even if they changed on disk since they were read.
+    pub force: bool,
+    /// Read files that aren't valid UTF-8 anyway, replacing invalid bytes.
+    pub lossy: bool,
+    /// Skip reparsing rewritten content before writing it.
+    pub no_validate: bool,
+    /// Also write the run's changes and stats as JSON to this path.
+    pub summary_json_file: Option<PathBuf>,
+    /// Write the rewrites as a unified diff to this path instead of editing files in place.
+    pub patch_file: Option<PathBuf>,
+    /// Write rewritten layouts under this directory, mirroring their paths from the scan root,
+    /// instead of editing them in place.
+    pub output_dir: Option<PathBuf>,
+    /// What to do when a file already exists in `output_dir`.
+    pub on_conflict: ConflictPolicy,
+    /// Only look at files directly in the scanned directory, not in subdirectories.
+    pub no_recurse: bool,
+    /// Only process these layout files, such as the ones resolved from `--session`. Empty means
+    /// every layout found.
+    pub layouts: Vec<PathBuf>,
+    /// Process any `*.kdl` file with a top-level `layout` or `pane` node, instead of only
+    /// files named session-layout.kdl.
+    pub by_content: bool,
+    /// Stop discovery once more than this many layout files are found.
+    pub max_files: Option<usize>,
+    /// What to do when `max_files` is exceeded.
+    pub on_max_files: MaxFilesAction,
+    /// List changes per session directory in the summary instead of per file.
+    pub group_by_session: bool,
+    /// List changes as a tree of sessions, files and panes in the summary instead of per file.
+    pub tree: bool,
+    /// Also list this many changes with the longest original commands.
+    pub report_largest: Option<usize>,
+    /// Only detail this many changes in verbose output, summarizing the rest as a count.
+    pub preview_count: Option<usize>,
+    /// Print the time spent in each phase of the run to stderr.
+    pub profile: bool,
+    /// After the summary, tally the extensions of the files the simplified commands open.
+    pub report_by_extension: bool,
+    /// Names to show editors under in the report, keyed by editor basename.
+    pub editor_aliases: BTreeMap<String, String>,
+    /// How the end-of-run report is printed. Machine-readable formats print even when quiet.
+    pub format: OutputFormat,
+    /// Minimum time between two layout writes, to spread out I/O on slow disks.
+    pub write_delay: Duration,
+    /// Give up on a layout whose rewrite takes longer than this, skipping it with a warning.
+    pub file_timeout: Option<Duration>,
+    /// Read this many layouts before rewriting any of them, to amortize latency on network
+    /// storage. `0` and `1` read and rewrite one file at a time.
+    pub read_batch: usize,
+    /// Write a `<file>.zch-report.json` with the changed commands next to each written layout.
+    pub sidecar_report: bool,
+    /// Generations of `<file>.bak` backups to keep of layouts rewritten in place, newest first
+    /// as `.bak`, `.bak.1`, ... `0` writes no backups.
+    pub keep_backups: usize,
+    /// Log file to append a line to after writing changes. `None` means [`LOG_PATH`].
+    pub log_file: Option<PathBuf>,
+    /// How pane commands are rewritten.
+    pub transform: TransformOptions,
+}
+
+impl ScanOptions {
+    /// Starts building options from the CLI defaults.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use zellij_command_hook::scan::{ScanOptions, compute_changes};
+    ///
+    /// let options = ScanOptions::builder()
+    ///     .dry_run(true)
+    ///     .max_files(Some(10))
+    ///     .build();
+    /// let plan = compute_changes(Path::new("tests/fixtures"), &options);
+    /// assert!(!plan.files.is_empty());
+    /// ```
+    pub fn builder() -> ScanOptionsBuilder {
+        ScanOptionsBuilder::default()
+    }
+}
+
+/// Builds [`ScanOptions`] field by field; see [`ScanOptions::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptionsBuilder {
+    options: ScanOptions,
+}
+
+impl ScanOptionsBuilder {
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.options.verbose = verbose;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
+
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.options.quiet = quiet;
+        self
+    }
+
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.options.interactive = interactive;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.options.force = force;
+        self
+    }
+
+    pub fn lossy(mut self, lossy: bool) -> Self {
+        self.options.lossy = lossy;
+        self
+    }
+
+    pub fn no_validate(mut self, no_validate: bool) -> Self {
+        self.options.no_validate = no_validate;
+        self
+    }
+
+    pub fn summary_json_file(mut self, path: Option<PathBuf>) -> Self {
+        self.options.summary_json_file = path;
+        self
+    }
+
+    pub fn patch_file(mut self, path: Option<PathBuf>) -> Self {
+        self.options.patch_file = path;
+        self
+    }
+
+    pub fn output_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.options.output_dir = dir;
+        self
+    }
+
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.options.on_conflict = policy;
+        self
+    }
+
+    pub fn no_recurse(mut self, no_recurse: bool) -> Self {
+        self.options.no_recurse = no_recurse;
+        self
+    }
+
+    pub fn layouts(mut self, layouts: Vec<PathBuf>) -> Self {
+        self.options.layouts = layouts;
+        self
+    }
+
+    pub fn by_content(mut self, by_content: bool) -> Self {
+        self.options.by_content = by_content;
+        self
+    }
+
+    pub fn max_files(mut self, max_files: Option<usize>) -> Self {
+        self.options.max_files = max_files;
+        self
+    }
+
+    pub fn on_max_files(mut self, action: MaxFilesAction) -> Self {
+        self.options.on_max_files = action;
+        self
+    }
+
+    pub fn group_by_session(mut self, group_by_session: bool) -> Self {
+        self.options.group_by_session = group_by_session;
+        self
+    }
+
+    pub fn report_largest(mut self, count: Option<usize>) -> Self {
+        self.options.report_largest = count;
+        self
+    }
+
+    pub fn preview_count(mut self, count: Option<usize>) -> Self {
+        self.options.preview_count = count;
+        self
+    }
+
+    pub fn profile(mut self, profile: bool) -> Self {
+        self.options.profile = profile;
+        self
+    }
+
+    pub fn editor_aliases(mut self, aliases: BTreeMap<String, String>) -> Self {
+        self.options.editor_aliases = aliases;
+        self
+    }
+
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.options.format = format;
+        self
+    }
+
+    pub fn write_delay(mut self, delay: Duration) -> Self {
+        self.options.write_delay = delay;
+        self
+    }
+
+    pub fn tree(mut self, tree: bool) -> Self {
+        self.options.tree = tree;
+        self
+    }
+
+    pub fn report_by_extension(mut self, report_by_extension: bool) -> Self {
+        self.options.report_by_extension = report_by_extension;
+        self
+    }
+
+    pub fn file_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.file_timeout = timeout;
+        self
+    }
+
+    pub fn read_batch(mut self, read_batch: usize) -> Self {
+        self.options.read_batch = read_batch;
+        self
+    }
+
+    pub fn sidecar_report(mut self, sidecar_report: bool) -> Self {
+        self.options.sidecar_report = sidecar_report;
+        self
+    }
+
+    pub fn keep_backups(mut self, generations: usize) -> Self {
+        self.options.keep_backups = generations;
+        self
+    }
+
+    pub fn log_file(mut self, path: Option<PathBuf>) -> Self {
+        self.options.log_file = path;
+        self
+    }
+
+    pub fn transform(mut self, transform: TransformOptions) -> Self {
+        self.options.transform = transform;
+        self
+    }
+
+    pub fn build(self) -> ScanOptions {
+        self.options
+    }
+}
+
+/// What to do when a file being written to `--output-dir` already exists there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Leave the existing file alone
+    Skip,
+    /// Abort the run before writing anything
+    Error,
+}
+
+/// Format of the report printed at the end of a scan.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Summary for reading in a terminal
+    #[default]
+    Human,
+    /// The summary JSON, as written by --summary-json-file
+    Json,
+    /// One row per change: file_path,original_command,simplified_command,bytes_saved
+    Csv,
+}
+
+/// What to do when a scan finds more layout files than `--max-files` allows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MaxFilesAction {
+    /// Abort the scan without processing anything
+    #[default]
+    Error,
+    /// Warn and process only the first N files
+    Warn,
+}
+
+/// Proposed rewrite of a single layout file, computed before anything is written.
+#[derive(Debug, Clone)]
+pub struct FileChanges {
+    pub file_path: String,
+    pub original_content: String,
+    pub modified_content: String,
+    pub changes: Vec<Changes>,
+    /// Modification time when the file was read, used to detect concurrent rewrites.
+    pub mtime: Option<SystemTime>,
+    /// Permissions when the file was read, carried over to the rewritten file.
+    pub permissions: std::fs::Permissions,
+}
+
+/// Files with proposed changes, and any per-file errors hit while computing them.
+#[derive(Debug, Default)]
+pub struct ScanPlan {
+    pub files: Vec<FileChanges>,
+    pub errors: Vec<ScanError>,
+    /// Number of layout files found, whether or not they need changes.
+    pub discovered: usize,
+    /// Layout files with editor panes that needed no changes.
+    pub already_simplified: usize,
+    /// Layout files that were deleted between discovery and reading.
+    pub vanished: usize,
+    /// Layout files skipped because rewriting them took longer than `file_timeout`.
+    pub timed_out: Vec<String>,
+    /// Discovery stopped early because `max_files` was exceeded.
+    pub limit_reached: bool,
+    /// Time spent discovering, reading and transforming files.
+    pub timings: Timings,
+}
+
+/// Time spent in each phase of a scan, for `--profile`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    /// Walking directories to find layout files.
+    pub discover: Duration,
+    /// Reading layout files.
+    pub read: Duration,
+    /// Parsing and rewriting layouts.
+    pub transform: Duration,
+    /// Writing layouts, or the patch file.
+    pub write: Duration,
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Profile:")?;
+        for (phase, elapsed) in [
+            ("discover", self.discover),
+            ("read", self.read),
+            ("transform", self.transform),
+            ("write", self.write),
+        ] {
+            writeln!(f, "  {:<10} {:?}", phase, elapsed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of a scan: the changes found and any per-file errors encountered along the way.
+#[derive(Debug, Default)]
+pub struct ScanOutcome {
+    pub changes: Vec<Changes>,
+    /// Files left untouched because they changed on disk during the run.
+    pub skipped: Vec<String>,
+    /// Layout files with editor panes that needed no changes.
+    pub already_simplified: usize,
+    /// Layout files that were deleted between discovery and reading.
+    pub vanished: usize,
+    /// Layout files skipped because rewriting them took longer than `file_timeout`.
+    pub timed_out: Vec<String>,
+    pub errors: Vec<ScanError>,
+}
+
+/// What scanning a single layout file found.
+#[derive(Debug)]
+enum FileScan {
+    Changed(FileChanges),
+    /// The file has editor panes, but they are already simplified.
+    AlreadySimplified,
+    /// The file has no editor panes to simplify.
+    NoEditorPanes,
+    /// The file was deleted after it was discovered, e.g. by zellij.
+    Vanished,
+    /// Rewriting the file took longer than `file_timeout`.
+    TimedOut,
+}
+
+/// Scans a directory recursively for session-layout.kdl files and simplifies nvim commands.
+/// Per-file failures don't stop the scan; they are collected in the returned outcome.
+pub fn scan_layouts(dir_path: &str, options: &ScanOptions) -> Result<ScanOutcome, ScanError> {
+    let path = Path::new(dir_path);
+    if !path.is_dir() {
+        return Err(ScanError::NotADirectory {
+            path: dir_path.to_string(),
+        });
+    }
+
+    let writes = !options.dry_run && options.patch_file.is_none();
+    let in_place = writes && options.output_dir.is_none();
+    if options.interactive && in_place && !std::io::stdin().is_terminal() {
+        return Err(ScanError::NotATerminal);
+    }
+
+    if options.dry_run && !options.quiet {
+        println!("===============DRY RUN===============");
+    }
+
+    if !options.quiet {
+        let kind = if options.by_content {
+            "*.kdl layout"
+        } else {
+            "session-layout.kdl"
+        };
+        println!("Scanning {} for {} files...", dir_path, kind);
+    }
+
+    let plan = compute_changes(path, options);
+    if plan.limit_reached
+        && let Some(limit) = options.max_files
+    {
+        match options.on_max_files {
+            MaxFilesAction::Error => {
+                return Err(ScanError::TooManyFiles {
+                    path: dir_path.to_string(),
+                    limit,
+                });
+            }
+            MaxFilesAction::Warn => {
+                if !options.quiet {
+                    options.transform.diagnostics.warn(
+                        &format!(
+                            "more than {} layout files under {}, processing only the first {}",
+                            limit, dir_path, limit
+                        ),
+                        Some(dir_path),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut timings = plan.timings;
+    let write_start = Instant::now();
+    let mut outcome = if in_place {
+        let plan = if options.interactive {
+            let mut stdout = std::io::stdout();
+            match review_changes(plan, path, &mut std::io::stdin().lock(), &mut stdout) {
+                Ok(plan) => plan,
+                Err(source) => {
+                    return Err(ScanError::Read {
+                        path: "<stdin>".to_string(),
+                        source,
+                    });
+                }
+            }
+        } else {
+            plan
+        };
+        apply_changes(plan, options)
+    } else if writes && let Some(output_dir) = &options.output_dir {
+        write_to_output_dir(plan, path, output_dir, options)?
+    } else {
+        let mut outcome = ScanOutcome {
+            errors: plan.errors,
+            already_simplified: plan.already_simplified,
+            vanished: plan.vanished,
+            timed_out: plan.timed_out,
+            ..Default::default()
+        };
+        if let Some(patch_path) = &options.patch_file
+            && let Err(e) = write_patch(patch_path, path, &plan.files)
+        {
+            outcome.errors.push(e);
+        }
+        outcome.changes = plan.files.into_iter().flat_map(|f| f.changes).collect();
+        outcome
+    };
+    timings.write = write_start.elapsed();
+
+    print_report(&outcome, options, !writes);
+    if options.profile && !options.quiet {
+        eprint!("{}", timings);
+    }
+
+    if let Some(json_path) = &options.summary_json_file
+        && let Err(e) = write_summary_json(json_path, &outcome, options.dry_run)
+    {
+        outcome.errors.push(e);
+    }
+
+    // Log to file (only if files were written)
+    if in_place
+        && !outcome.changes.is_empty()
+        && let Ok(logger) =
+            Logger::shared(options.log_file.as_deref().unwrap_or(Path::new(LOG_PATH)))
+    {
+        let _ = logger.log(&processed_log_entry(outcome.changes.len()));
+    }
+
+    Ok(outcome)
+}
+
+/// Finds the layout of a named session in zellij's cache dir, at
+/// `<cache_dir>/<version>/session_info/<name>/session-layout.kdl`. If several zellij versions
+/// have the session, the most recently modified layout wins.
+pub fn resolve_session_layout(cache_dir: &Path, name: &str) -> Result<PathBuf, ScanError> {
+    let not_found = || ScanError::SessionNotFound {
+        name: name.to_string(),
+        cache_dir: cache_dir.display().to_string(),
+    };
+    let versions = std::fs::read_dir(cache_dir).map_err(|_| not_found())?;
+
+    versions
+        .flatten()
+        .map(|version| {
+            version
+                .path()
+                .join("session_info")
+                .join(name)
+                .join("session-layout.kdl")
+        })
+        .filter_map(|layout| {
+            let mtime = std::fs::metadata(&layout).and_then(|m| m.modified()).ok()?;
+            Some((mtime, layout))
+        })
+        .max()
+        .map(|(_, layout)| layout)
+        .ok_or_else(not_found)
+}
+
+/// Audits the layouts inside a zip archive of zellij caches without extracting it. The archive
+/// is only rewritten if `rewrite` is set, with every entry stored uncompressed.
+pub fn scan_zip(
+    zip_path: &Path,
+    rewrite: bool,
+    options: &ScanOptions,
+) -> Result<ScanOutcome, ScanError> {
+    let archive = zip_path.display().to_string();
+    let read_error = |source| ScanError::Read {
+        path: archive.clone(),
+        source,
+    };
+    let permissions = std::fs::metadata(zip_path)
+        .map_err(read_error)?
+        .permissions();
+    let bytes = std::fs::read(zip_path).map_err(read_error)?;
+    let mut entries = read_archive(&bytes).map_err(|message| ScanError::Parse {
+        path: archive.clone(),
+        message,
+    })?;
+
+    if !options.quiet {
+        println!("Scanning {} for session-layout.kdl files...", archive);
+    }
+
+    let mut outcome = ScanOutcome::default();
+    for entry in &mut entries {
+        if Path::new(&entry.name).file_name() != Some("session-layout.kdl".as_ref()) {
+            continue;
+        }
+        let entry_path = format!("{}/{}", archive, entry.name);
+        let content = match String::from_utf8(entry.data.clone()) {
+            Ok(content) => content,
+            Err(e) if options.lossy => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            Err(e) => {
+                outcome.errors.push(ScanError::Encoding {
+                    path: entry_path,
+                    valid_up_to: e.utf8_error().valid_up_to(),
+                });
+                continue;
+            }
+        };
+
+        let (modified, changes) = match try_process_kdl_content(&content, &options.transform) {
+            Ok(result) => result,
+            Err(e) => {
+                outcome.errors.push(ScanError::Parse {
+                    path: entry_path,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if changes.is_empty() || modified == content {
+            if has_editor_panes(&content, &options.transform) {
+                outcome.already_simplified += 1;
+            }
+            continue;
+        }
+        if rewrite {
+            if !options.no_validate
+                && let Err(e) = modified.parse::<KdlDocument>()
+            {
+                outcome.errors.push(ScanError::InvalidOutput {
+                    path: entry_path,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+            entry.data = modified.into_bytes();
+        }
+        outcome
+            .changes
+            .extend(changes.into_iter().map(|change| Changes {
+                file_path: entry_path.clone(),
+                ..change
+            }));
+    }
+
+    if rewrite
+        && !outcome.changes.is_empty()
+        && let Err(source) = write_atomic(zip_path, write_archive(&entries), &permissions)
+    {
+        outcome.errors.push(ScanError::Write {
+            path: archive.clone(),
+            source,
+        });
+        outcome.changes.clear();
+    }
+
+    print_report(&outcome, options, !rewrite);
+    Ok(outcome)
+}
+
+/// What a read-only inspection found in the layouts under a directory, and the files that
+/// couldn't be inspected.
+#[derive(Debug)]
+pub struct InspectionReport<T> {
+    pub found: Vec<T>,
+    pub errors: Vec<ScanError>,
+}
+
+/// Finds nvim panes within the same tab that open overlapping files, in every layout under
+/// `dir_path`. Read-only: nothing is written.
+pub fn find_redundant_panes(
+    dir_path: &str,
+    options: &ScanOptions,
+) -> Result<InspectionReport<RedundantPanes>, ScanError> {
+    inspect_layouts(dir_path, options, |file_path, doc| {
+        let mut found = redundant_panes(doc, &options.transform);
+        for redundant in &mut found {
+            redundant.file_path = file_path.to_string();
+        }
+        found
+    })
+}
+
+/// Finds panes that mention a known editor but aren't simplified, in every layout under
+/// `dir_path`. Read-only: nothing is written.
+pub fn find_unrecognized_panes(
+    dir_path: &str,
+    options: &ScanOptions,
+) -> Result<InspectionReport<UnrecognizedPane>, ScanError> {
+    inspect_layouts(dir_path, options, |file_path, doc| {
+        let mut found = unrecognized_panes(doc, &options.transform);
+        for pane in &mut found {
+            pane.file_path = file_path.to_string();
+        }
+        found
+    })
+}
+
+/// A layout with no panes, as found by [`find_empty_sessions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmptySession {
+    pub file_path: String,
+}
+
+impl fmt::Display for EmptySession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: no panes", self.file_path)
+    }
+}
+
+/// Finds layouts under `dir_path` without any pane, such as empty or stub session layouts.
+/// Read-only: nothing is written.
+pub fn find_empty_sessions(
+    dir_path: &str,
+    options: &ScanOptions,
+) -> Result<InspectionReport<EmptySession>, ScanError> {
+    inspect_layouts(dir_path, options, |file_path, doc| {
+        if has_panes(doc) {
+            Vec::new()
+        } else {
+            vec![EmptySession {
+                file_path: file_path.to_string(),
+            }]
+        }
+    })
+}
+
+/// Parses every layout under `dir_path` and collects what `inspect` finds in each.
+fn inspect_layouts<T>(
+    dir_path: &str,
+    options: &ScanOptions,
+    mut inspect: impl FnMut(&str, &KdlDocument) -> Vec<T>,
+) -> Result<InspectionReport<T>, ScanError> {
+    let path = Path::new(dir_path);
+    if !path.is_dir() {
+        return Err(ScanError::NotADirectory {
+            path: dir_path.to_string(),
+        });
+    }
+
+    let mut files = Vec::new();
+    find_layout_files(path, options, &mut files);
+
+    let mut report = InspectionReport {
+        found: Vec::new(),
+        errors: Vec::new(),
+    };
+    for file in files {
+        let file_path = file.display().to_string();
+        let content = match std::fs::read(&file) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(source) => {
+                report.errors.push(ScanError::Read {
+                    path: file_path,
+                    source,
+                });
+                continue;
+            }
+        };
+        match content.parse::<KdlDocument>() {
+            Ok(doc) => report.found.extend(inspect(&file_path, &doc)),
+            Err(e) => report.errors.push(ScanError::Parse {
+                path: file_path,
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Collects the layout files under `dir`, sorted by path.
+fn find_layout_files(dir: &Path, options: &ScanOptions, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            if !options.no_recurse {
+                find_layout_files(&path, options, files);
+            }
+        } else if is_layout_file(&path, options) {
+            files.push(path);
+        }
+    }
+}
+
+/// Whether a file found during discovery should be processed: any file named
+/// session-layout.kdl, or with `options.by_content`, any `*.kdl` file that parses with a
+/// top-level `layout` or `pane` node. With `options.layouts`, exactly those files count.
+fn is_layout_file(path: &Path, options: &ScanOptions) -> bool {
+    if !options.layouts.is_empty() {
+        return options.layouts.iter().any(|layout| layout == path);
+    }
+    if !options.by_content {
+        return path.file_name().and_then(|n| n.to_str()) == Some("session-layout.kdl");
+    }
+    if path.extension().and_then(|e| e.to_str()) != Some("kdl") {
+        return false;
+    }
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return false;
+    };
+    content.parse::<KdlDocument>().is_ok_and(|doc| {
+        doc.nodes()
+            .iter()
+            .any(|node| matches!(node.name().value(), "layout" | "pane"))
+    })
+}
+
+/// Finds layout files under `dir` and computes their proposed changes without writing anything.
+/// Discovery stops as soon as more than `options.max_files` files are found.
+pub fn compute_changes(dir: &Path, options: &ScanOptions) -> ScanPlan {
+    compute_changes_with(dir, options, &mut |_| {})
+}
+
+/// Like [`compute_changes`], calling `on_change` with each change as soon as its file has been
+/// processed, for embedders that want to stream changes rather than wait for the plan.
+pub fn compute_changes_with(
+    dir: &Path,
+    options: &ScanOptions,
+    on_change: &mut dyn FnMut(&Changes),
+) -> ScanPlan {
+    let mut plan = ScanPlan::default();
+    let mut batch = Vec::new();
+    scan_dir_recursive(dir, &mut plan, options, &mut batch, on_change);
+    process_batch(&mut batch, &mut plan, options, on_change);
+    plan
+}
+
+/// Writes planned changes to disk. Files that changed on disk since they were read are
+/// skipped rather than clobbered, unless `options.force` is set. Rewritten content that
+/// doesn't parse as KDL is never written, unless `options.no_validate` is set.
+pub fn apply_changes(plan: ScanPlan, options: &ScanOptions) -> ScanOutcome {
+    let mut outcome = ScanOutcome {
+        errors: plan.errors,
+        already_simplified: plan.already_simplified,
+        vanished: plan.vanished,
+        timed_out: plan.timed_out,
+        ..Default::default()
+    };
+
+    let mut last_write = None;
+    for file in plan.files {
+        if file.modified_content == file.original_content {
+            continue;
+        }
+        if !options.force && modified_since_read(&file) {
+            if !options.quiet {
+                options.transform.diagnostics.warn(
+                    &format!(
+                        "{} was modified during the run, skipping (use --force to overwrite)",
+                        file.file_path
+                    ),
+                    Some(&file.file_path),
+                );
+            }
+            outcome.skipped.push(file.file_path);
+            continue;
+        }
+
+        if !options.no_validate
+            && let Err(e) = validate_output(&file)
+        {
+            outcome.errors.push(e);
+            continue;
+        }
+
+        let path = Path::new(&file.file_path);
+        if let Err(source) = rotate_backups(path, options.keep_backups) {
+            outcome.errors.push(ScanError::Write {
+                path: file.file_path,
+                source,
+            });
+            continue;
+        }
+        pace_write(&mut last_write, options.write_delay);
+        match write_atomic(path, &file.modified_content, &file.permissions) {
+            Ok(()) => {
+                if options.sidecar_report
+                    && let Err(e) = write_sidecar_report(path, &file.changes)
+                {
+                    outcome.errors.push(e);
+                }
+                outcome.changes.extend(file.changes);
+            }
+            Err(source) => outcome.errors.push(ScanError::Write {
+                path: file.file_path,
+                source,
+            }),
+        }
+    }
+
+    outcome
+}
+
+/// Copies `path` to `<path>.bak` before it's rewritten, first shifting older backups along to
+/// `.bak.1`, `.bak.2`, ... so that `generations` are kept. Does nothing for `0` generations.
+fn rotate_backups(path: &Path, generations: usize) -> std::io::Result<()> {
+    if generations == 0 {
+        return Ok(());
+    }
+    let backup = |generation: usize| {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".bak");
+        if generation > 0 {
+            name.push(format!(".{}", generation));
+        }
+        path.with_file_name(name)
+    };
+
+    for generation in (1..generations).rev() {
+        let older = backup(generation - 1);
+        if older.exists() {
+            std::fs::rename(&older, backup(generation))?;
+        }
+    }
+    std::fs::copy(path, backup(0)).map(drop)
+}
+
+/// What [`clean_artifacts`] removed, or would remove, and the files it failed to remove.
+#[derive(Debug, Default)]
+pub struct CleanOutcome {
+    pub removed: Vec<PathBuf>,
+    pub errors: Vec<ScanError>,
+}
+
+/// Removes the files the tool writes next to layouts (see [`is_artifact`]) under `dir`,
+/// recursively. With `dry_run`, only lists them.
+pub fn clean_artifacts(dir: &Path, dry_run: bool) -> Result<CleanOutcome, ScanError> {
+    if !dir.is_dir() {
+        return Err(ScanError::NotADirectory {
+            path: dir.display().to_string(),
+        });
+    }
+
+    let mut artifacts = Vec::new();
+    find_artifacts(dir, &mut artifacts);
+    let mut outcome = CleanOutcome::default();
+    for path in artifacts {
+        if dry_run {
+            outcome.removed.push(path);
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => outcome.removed.push(path),
+            Err(source) => outcome.errors.push(ScanError::Remove {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+    Ok(outcome)
+}
+
+fn find_artifacts(dir: &Path, artifacts: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            find_artifacts(&path, artifacts);
+        } else if is_artifact(&path) {
+            artifacts.push(path);
+        }
+    }
+}
+
+/// Whether `path` is a file the tool writes next to a `*.kdl` layout: a `--keep-backups`
+/// backup (`<layout>.bak`, `<layout>.bak.N`) or a `--sidecar-report` file
+/// (`<layout>.zch-report.json`).
+pub fn is_artifact(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let layout = if let Some(layout) = name.strip_suffix(".zch-report.json") {
+        layout
+    } else if let Some(layout) = name.strip_suffix(".bak") {
+        layout
+    } else if let Some((layout, generation)) = name.rsplit_once(".bak.")
+        && !generation.is_empty()
+        && generation.bytes().all(|b| b.is_ascii_digit())
+    {
+        layout
+    } else {
+        return false;
+    };
+    layout.len() > ".kdl".len() && layout.ends_with(".kdl")
+}
+
+/// Waits until `delay` has passed since the previous write, then records this one.
+fn pace_write(last_write: &mut Option<Instant>, delay: Duration) {
+    if let Some(last) = last_write {
+        let elapsed = last.elapsed();
+        if elapsed < delay {
+            std::thread::sleep(delay - elapsed);
+        }
+    }
+    *last_write = Some(Instant::now());
+}
+
+/// Writes `<layout>.zch-report.json` next to a rewritten layout, with a timestamp and the
+/// original and simplified command of every changed pane.
+fn write_sidecar_report(layout: &Path, changes: &[Changes]) -> Result<(), ScanError> {
+    let mut name = layout.file_name().unwrap_or_default().to_os_string();
+    name.push(".zch-report.json");
+    let path = layout.with_file_name(name);
+    let json = Json::object([
+        ("file", Json::from(layout.display().to_string())),
+        ("timestamp", Json::from(Local::now().to_rfc3339())),
+        (
+            "changes",
+            Json::from(changes.iter().map(Changes::to_json).collect::<Vec<_>>()),
+        ),
+    ]);
+    std::fs::write(&path, format!("{}\n", json)).map_err(|source| ScanError::Write {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+/// Shows the diff of each planned rewrite and asks whether to apply it, skip it, or quit.
+/// Returns the plan with only the accepted files; quitting (or end of input) drops the rest.
+pub fn review_changes(
+    mut plan: ScanPlan,
+    root: &Path,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<ScanPlan> {
+    let mut accepted = Vec::new();
+    'files: for file in std::mem::take(&mut plan.files) {
+        let file_path = Path::new(&file.file_path);
+        let relative = file_path.strip_prefix(root).unwrap_or(file_path);
+        write!(
+            output,
+            "\n{}",
+            unified_diff(
+                &relative.display().to_string(),
+                &file.original_content,
+                &file.modified_content,
+            )
+        )?;
+
+        loop {
+            write!(
+                output,
+                "Apply changes to {}? [a]pply / [s]kip / [q]uit ",
+                file.file_path
+            )?;
+            output.flush()?;
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                break 'files;
+            }
+            match answer.trim() {
+                "a" => {
+                    accepted.push(file);
+                    break;
+                }
+                "s" => break,
+                "q" => break 'files,
+                _ => {}
+            }
+        }
+    }
+    plan.files = accepted;
+    Ok(plan)
+}
+
+/// Checks that a rewritten layout still parses as KDL.
+fn validate_output(file: &FileChanges) -> Result<(), ScanError> {
+    file.modified_content
+        .parse::<KdlDocument>()
+        .map(|_| ())
+        .map_err(|e| ScanError::InvalidOutput {
+            path: file.file_path.clone(),
+            message: e.to_string(),
+        })
+}
+
+/// Writes planned changes under `output_dir` at their path relative to `root`, leaving the
+/// originals untouched. Existing outputs are handled by `options.on_conflict`; with
+/// [`ConflictPolicy::Error`] nothing is written if any output already exists.
+fn write_to_output_dir(
+    plan: ScanPlan,
+    root: &Path,
+    output_dir: &Path,
+    options: &ScanOptions,
+) -> Result<ScanOutcome, ScanError> {
+    let destination = |file: &FileChanges| {
+        let file_path = Path::new(&file.file_path);
+        output_dir.join(file_path.strip_prefix(root).unwrap_or(file_path))
+    };
+    if options.on_conflict == ConflictPolicy::Error
+        && let Some(existing) = plan.files.iter().map(destination).find(|d| d.exists())
+    {
+        return Err(ScanError::OutputExists {
+            path: existing.display().to_string(),
+        });
+    }
+
+    let mut outcome = ScanOutcome {
+        errors: plan.errors,
+        already_simplified: plan.already_simplified,
+        vanished: plan.vanished,
+        timed_out: plan.timed_out,
+        ..Default::default()
+    };
+    let mut last_write = None;
+    for file in plan.files {
+        if file.modified_content == file.original_content {
+            continue;
+        }
+        let dest = destination(&file);
+        if options.on_conflict == ConflictPolicy::Skip && dest.exists() {
+            if !options.quiet {
+                let dest = dest.display().to_string();
+                options
+                    .transform
+                    .diagnostics
+                    .warn(&format!("{} already exists, skipping", dest), Some(&dest));
+            }
+            continue;
+        }
+        if !options.no_validate
+            && let Err(e) = validate_output(&file)
+        {
+            outcome.errors.push(e);
+            continue;
+        }
+
+        pace_write(&mut last_write, options.write_delay);
+        let written = match dest.parent() {
+            Some(parent) => std::fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|()| write_atomic(&dest, &file.modified_content, &file.permissions));
+        match written {
+            Ok(()) => {
+                if options.sidecar_report
+                    && let Err(e) = write_sidecar_report(&dest, &file.changes)
+                {
+                    outcome.errors.push(e);
+                }
+                outcome.changes.extend(file.changes);
+            }
+            Err(source) => outcome.errors.push(ScanError::Write {
+                path: dest.display().to_string(),
+                source,
+            }),
+        }
+    }
+    Ok(outcome)
+}
+
+/// Replaces `path` with `content` by writing a temp file next to it and renaming it over
+/// the original, so readers never see a half-written layout. The temp file gets the original's
+/// permissions before the rename.
+fn write_atomic(
+    path: &Path,
+    content: impl AsRef<[u8]>,
+    permissions: &std::fs::Permissions,
+) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = std::fs::write(&temp_path, content)
+        .and_then(|()| std::fs::set_permissions(&temp_path, permissions.clone()))
+        .and_then(|()| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Checks if a file's mtime differs from the one recorded when it was read.
+fn modified_since_read(file: &FileChanges) -> bool {
+    let current = std::fs::metadata(&file.file_path)
+        .and_then(|m| m.modified())
+        .ok();
+    current != file.mtime
+}
+
+/// Paths of the files with changes, each once, in the order they were processed.
+pub fn changed_files(outcome: &ScanOutcome) -> Vec<&str> {
+    let mut seen = BTreeSet::new();
+    outcome
+        .changes
+        .iter()
+        .map(|c| c.file_path.as_str())
+        .filter(|path| seen.insert(*path))
+        .collect()
+}
+
+/// Builds the machine-readable report of a run: aggregate stats plus every change.
+pub(crate) fn summary_json(outcome: &ScanOutcome, dry_run: bool) -> Json {
+    let files: BTreeSet<&str> = outcome
+        .changes
+        .iter()
+        .map(|c| c.file_path.as_str())
+        .collect();
+
+    Json::object([
+        (
+            "stats",
+            Json::object([
+                ("dry_run", Json::from(dry_run)),
+                ("files", Json::from(files.len())),
+                ("changes", Json::from(outcome.changes.len())),
+                ("skipped", Json::from(outcome.skipped.len())),
+                ("timed_out", Json::from(outcome.timed_out.len())),
+                ("errors", Json::from(outcome.errors.len())),
+            ]),
+        ),
+        (
+            "changes",
+            Json::from(
+                outcome
+                    .changes
+                    .iter()
+                    .map(Changes::to_json)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+    ])
+}
+
+/// Writes the JSON report to `path`, creating parent directories as needed.
+fn write_summary_json(path: &Path, outcome: &ScanOutcome, dry_run: bool) -> Result<(), ScanError> {
+    let write_error = |source| ScanError::Write {
+        path: path.display().to_string(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    let json = summary_json(outcome, dry_run);
+    std::fs::write(path, format!("{}\n", json)).map_err(write_error)
+}
+
+/// Writes one unified diff covering every planned rewrite, with paths relative to `root`
+/// so it applies with `git apply` or `patch -p1` from there.
+fn write_patch(path: &Path, root: &Path, files: &[FileChanges]) -> Result<(), ScanError> {
+    let patch: String = files
+        .iter()
+        .map(|file| {
+            let file_path = Path::new(&file.file_path);
+            let relative = file_path.strip_prefix(root).unwrap_or(file_path);
+            unified_diff(
+                &relative.display().to_string(),
+                &file.original_content,
+                &file.modified_content,
+            )
+        })
+        .collect();
+
+    let write_error = |source| ScanError::Write {
+        path: path.display().to_string(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    std::fs::write(path, patch).map_err(write_error)
+}
+
+/// Reads a whole layout document from stdin and writes the simplified document to stdout, or
+/// with `dry_run`, a unified diff of the proposed changes. Changes are reported on stderr so
+/// stdout stays a valid KDL document or patch.
+pub fn process_stdin(
+    verbose: bool,
+    dry_run: bool,
+    options: &TransformOptions,
+) -> Result<(), ScanError> {
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .map_err(|source| ScanError::Read {
+            path: "<stdin>".to_string(),
+            source,
+        })?;
+
+    let (modified, changes) = process_kdl_content_with(&content, options);
+    if dry_run {
+        print!("{}", unified_diff("stdin", &content, &modified));
+    } else {
+        print!("{}", modified);
+    }
+
+    if verbose {
+        eprintln!("{} change(s)", changes.len());
+        for (idx, change) in changes.iter().enumerate() {
+            eprintln!("\n{}. Original: {}", idx + 1, change.original_command);
+            eprintln!("   Simplified: {}", change.simplified_command);
+        }
+    }
+
+    Ok(())
+}
+
+/// Simplifies `content` and compares the result with `expected`, as the `verify` subcommand
+/// does. Returns a unified diff labelled `path` if they differ, or `None` if they match.
+pub fn verify_content(
+    path: &str,
+    content: &str,
+    expected: &str,
+    options: &TransformOptions,
+) -> Result<Option<String>, KdlError> {
+    let (simplified, _) = try_process_kdl_content(content, options)?;
+    Ok((simplified != expected).then(|| unified_diff(path, expected, &simplified)))
+}
+
+/// Prints the end-of-run report in `options.format`.
+fn print_report(outcome: &ScanOutcome, options: &ScanOptions, dry_run: bool) {
+    match options.format {
+        OutputFormat::Human if !options.quiet => print_summary(outcome, options, dry_run),
+        OutputFormat::Human => {}
+        OutputFormat::Json => println!("{}", summary_json(outcome, dry_run)),
+        OutputFormat::Csv => print!("{}", changes_csv(&outcome.changes)),
+    }
+}
+
+/// Renders changes as CSV with a header row. Fields containing commas, quotes or line breaks
+/// are quoted, with inner quotes doubled.
+pub fn changes_csv(changes: &[Changes]) -> String {
+    let mut out = String::from("file_path,original_command,simplified_command,bytes_saved\n");
+    for change in changes {
+        let fields = [
+            csv_field(&change.file_path),
+            csv_field(&change.original_command),
+            csv_field(&change.simplified_command),
+            change.bytes_saved().to_string(),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prints a summary of changes found and applied.
+fn print_summary(outcome: &ScanOutcome, options: &ScanOptions, dry_run: bool) {
+    let verbose = options.verbose;
+    let changes = &outcome.changes;
+    if changes.is_empty() {
+        println!("\nNo changes needed.");
+    } else {
+        println!("\nFound {} file(s) to update.", changes.len());
+        if let Some(breakdown) = editor_breakdown(changes, &options.editor_aliases) {
+            println!("By editor: {}", breakdown);
+        }
+
+        if options.tree && !verbose {
+            for line in change_tree(changes) {
+                println!("  {}", line);
+            }
+        } else if options.group_by_session && !verbose {
+            println!("By session:");
+            for line in session_breakdown(changes) {
+                println!("  {}", line);
+            }
+        } else if !verbose && !dry_run {
+            println!("Files updated:");
+            for change in changes {
+                println!("  {}", change.file_path);
+            }
+        } else if dry_run && !verbose {
+            println!("Files that would be updated:");
+            for change in changes {
+                println!("  {}", change.file_path);
+            }
+        }
+
+        if verbose {
+            println!("\nDetailed changes:");
+            for line in detailed_changes(changes, options.preview_count) {
+                println!("{}", line);
+            }
+        }
+    }
+
+    if dry_run && verbose && !changes.is_empty() {
+        println!("\nEstimated savings:");
+        for (path, bytes) in savings_by_file(changes) {
+            println!("  {:>8} bytes  {}", bytes, path);
+        }
+    }
+
+    if let Some(count) = options.report_largest
+        && !changes.is_empty()
+    {
+        println!("\nLargest commands:");
+        for change in largest_changes(changes, count) {
+            println!(
+                "  {:>8} bytes  {}: {}",
+                change.original_command.len(),
+                change.file_path,
+                change.simplified_command
+            );
+        }
+    }
+
+    if options.report_by_extension
+        && let Some(breakdown) = extension_breakdown(changes)
+    {
+        println!("\nBy extension: {}", breakdown);
+    }
+
+    if outcome.already_simplified > 0 {
+        println!("{} file(s) already simplified", outcome.already_simplified);
+    }
+    if verbose && outcome.vanished > 0 {
+        println!("{} file(s) disappeared during the scan", outcome.vanished);
+    }
+
+    if !outcome.skipped.is_empty() {
+        println!("\nSkipped {} file(s):", outcome.skipped.len());
+        for path in &outcome.skipped {
+            println!("  {}: skipped (modified during run)", path);
+        }
+    }
+}
+
+/// Total bytes saved per file, biggest savings first.
+fn savings_by_file(changes: &[Changes]) -> Vec<(&str, usize)> {
+    let mut savings: BTreeMap<&str, usize> = BTreeMap::new();
+    for change in changes {
+        *savings.entry(change.file_path.as_str()).or_default() += change.bytes_saved();
+    }
+    let mut savings: Vec<(&str, usize)> = savings.into_iter().collect();
+    savings.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+    savings
+}
+
+/// Counts changed panes per editor, e.g. "nvim: 8 panes, vim: 1 pane", showing editors with
+/// an alias under that name. Returns `None` if no change was to an editor pane.
+fn editor_breakdown(changes: &[Changes], aliases: &BTreeMap<String, String>) -> Option<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for editor in changes.iter().filter_map(|c| c.editor.as_deref()) {
+        *counts.entry(editor).or_default() += 1;
+    }
+    if counts.is_empty() {
+        return None;
+    }
+
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|(editor, count)| {
+            let noun = if *count == 1 { "pane" } else { "panes" };
+            let name = aliases.get(*editor).map_or(*editor, String::as_str);
+            format!("{}: {} {}", name, count, noun)
+        })
+        .collect();
+    Some(parts.join(", "))
+}
+
+/// Tallies the extensions of the files opened by the changed commands, most common first, e.g.
+/// `rs: 40, md: 12`. Files without an extension aren't counted.
+fn extension_breakdown(changes: &[Changes]) -> Option<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for file in changes.iter().flat_map(|c| &c.files) {
+        if let Some(ext) = Path::new(file).extension().and_then(|e| e.to_str()) {
+            *counts.entry(ext).or_default() += 1;
+        }
+    }
+    if counts.is_empty() {
+        return None;
+    }
+
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|(ext, count)| format!("{}: {}", ext, count))
+        .collect();
+    Some(parts.join(", "))
+}
+
+/// The `count` changes with the longest original commands, longest first.
+fn largest_changes(changes: &[Changes], count: usize) -> Vec<&Changes> {
+    let mut sorted: Vec<&Changes> = changes.iter().collect();
+    sorted.sort_by_key(|change| std::cmp::Reverse(change.original_command.len()));
+    sorted.truncate(count);
+    sorted
+}
+
+/// Counts changes per session, named after the directory holding each layout file, as
+/// `session <name>: N changes` lines.
+fn session_breakdown(changes: &[Changes]) -> Vec<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for change in changes {
+        let session = Path::new(&change.file_path)
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or(&change.file_path);
+        *counts.entry(session).or_default() += 1;
+    }
+
+    counts
+        .iter()
+        .map(|(session, count)| {
+            let noun = if *count == 1 { "change" } else { "changes" };
+            format!("session {}: {} {}", session, count, noun)
+        })
+        .collect()
+}
+
+/// The verbose listing of each change's original and simplified command. With `preview_count`,
+/// only that many are listed and the rest are counted.
+fn detailed_changes(changes: &[Changes], preview_count: Option<usize>) -> Vec<String> {
+    let shown = preview_count.unwrap_or(changes.len()).min(changes.len());
+    let mut lines = Vec::new();
+    for (idx, change) in changes[..shown].iter().enumerate() {
+        lines.push(String::new());
+        lines.push(format!("{}. {}", idx + 1, change.file_path));
+        lines.push(format!("   Original: {}", change.original_command));
+        lines.push(format!("   Simplified: {}", change.simplified_command));
+    }
+    if shown < changes.len() {
+        lines.push(String::new());
+        lines.push(format!("... and {} more", changes.len() - shown));
+    }
+    lines
+}
+
+/// Lays out changes as an indented tree: session directory, then file, then pane, with the
+/// changes of each pane under it.
+fn change_tree(changes: &[Changes]) -> Vec<String> {
+    type Panes<'a> = BTreeMap<usize, Vec<&'a Changes>>;
+    let mut sessions: BTreeMap<&str, BTreeMap<&str, Panes>> = BTreeMap::new();
+    for change in changes {
+        let session = Path::new(&change.file_path)
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or(&change.file_path);
+        sessions
+            .entry(session)
+            .or_default()
+            .entry(&change.file_path)
+            .or_default()
+            .entry(change.pane)
+            .or_default()
+            .push(change);
+    }
+
+    let mut lines = Vec::new();
+    for (session, files) in sessions {
+        lines.push(format!("session {}", session));
+        for (file, panes) in files {
+            lines.push(format!("  {}", file));
+            for (pane, changes) in panes {
+                lines.push(format!("    pane {}", pane));
+                for change in changes {
+                    lines.push(format!(
+                        "      {} -> {}",
+                        change.original_command, change.simplified_command
+                    ));
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Recursively scans directories for session-layout.kdl files. Entries are visited in name
+/// order, directories and files together, so results don't depend on the filesystem.
+fn scan_dir_recursive(
+    dir: &Path,
+    plan: &mut ScanPlan,
+    options: &ScanOptions,
+    batch: &mut Vec<String>,
+    on_change: &mut dyn FnMut(&Changes),
+) {
+    let discover_start = Instant::now();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        paths.sort();
+        plan.timings.discover += discover_start.elapsed();
+        for path in paths {
+            if plan.limit_reached {
+                return;
+            }
+            if path.is_dir() {
+                if !options.no_recurse {
+                    scan_dir_recursive(&path, plan, options, batch, on_change);
+                }
+            } else if is_layout_file(&path, options)
+                && let Some(path_str) = path.to_str()
+            {
+                if options.max_files.is_some_and(|max| plan.discovered >= max) {
+                    plan.limit_reached = true;
+                    return;
+                }
+                plan.discovered += 1;
+                batch.push(path_str.to_string());
+                if batch.len() >= options.read_batch {
+                    process_batch(batch, plan, options, on_change);
+                }
+            }
+        }
+    }
+}
+
+/// Reads every file in `batch`, then simplifies them in order, emptying the batch.
+fn process_batch(
+    batch: &mut Vec<String>,
+    plan: &mut ScanPlan,
+    options: &ScanOptions,
+    on_change: &mut dyn FnMut(&Changes),
+) {
+    let reads: Vec<_> = batch
+        .iter()
+        .map(|path| read_layout_file(path, options, &mut plan.timings))
+        .collect();
+    for (path, read) in batch.drain(..).zip(reads) {
+        let scan =
+            read.and_then(|read| simplify_layout_file(&path, read, options, &mut plan.timings));
+        match scan {
+            Ok(FileScan::Changed(file)) => {
+                file.changes.iter().for_each(&mut *on_change);
+                plan.files.push(file);
+            }
+            Ok(FileScan::AlreadySimplified) => plan.already_simplified += 1,
+            Ok(FileScan::NoEditorPanes) => {}
+            Ok(FileScan::Vanished) => plan.vanished += 1,
+            Ok(FileScan::TimedOut) => plan.timed_out.push(path),
+            Err(e) => plan.errors.push(e),
+        }
+    }
+}
+
+/// A layout file as read from disk, before it is simplified.
+struct LayoutFile {
+    content: String,
+    mtime: Option<SystemTime>,
+    permissions: std::fs::Permissions,
+}
+
+/// Reads a single KDL file. Returns `None` if it was deleted since it was discovered.
+fn read_layout_file(
+    file_path: &str,
+    options: &ScanOptions,
+    timings: &mut Timings,
+) -> Result<Option<LayoutFile>, ScanError> {
+    if options.verbose {
+        if options.dry_run {
+            println!("Would process: {}", file_path);
+        } else {
+            println!("Processing: {}", file_path);
+        }
+    }
+
+    let read_start = Instant::now();
+    let read_error = |source| ScanError::Read {
+        path: file_path.to_string(),
+        source,
+    };
+    let read =
+        std::fs::metadata(file_path).and_then(|metadata| Ok((metadata, std::fs::read(file_path)?)));
+    let (metadata, bytes) = match read {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if options.verbose {
+                println!("Skipping {}: it no longer exists", file_path);
+            }
+            return Ok(None);
+        }
+        Err(source) => return Err(read_error(source)),
+    };
+    let mtime = metadata.modified().ok();
+    let permissions = metadata.permissions();
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) if options.lossy => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        Err(e) => {
+            return Err(ScanError::Encoding {
+                path: file_path.to_string(),
+                valid_up_to: e.utf8_error().valid_up_to(),
+            });
+        }
+    };
+
+    timings.read += read_start.elapsed();
+    Ok(Some(LayoutFile {
+        content,
+        mtime,
+        permissions,
+    }))
+}
+
+/// Simplifies a single KDL file read by [`read_layout_file`].
+fn simplify_layout_file(
+    file_path: &str,
+    read: Option<LayoutFile>,
+    options: &ScanOptions,
+    timings: &mut Timings,
+) -> Result<FileScan, ScanError> {
+    let Some(LayoutFile {
+        content,
+        mtime,
+        permissions,
+    }) = read
+    else {
+        return Ok(FileScan::Vanished);
+    };
+
+    let transform_start = Instant::now();
+    let mut transform = options.transform.clone();
+    if transform.canonicalize_paths {
+        transform.layout_dir = Path::new(file_path).parent().map(Path::to_path_buf);
+    }
+    let result = match options.file_timeout {
+        Some(timeout) => match process_with_timeout(&content, transform, timeout) {
+            Some(result) => result,
+            None => {
+                if !options.quiet {
+                    options.transform.diagnostics.warn(
+                        &format!(
+                            "{} timed out after {} ms, skipping",
+                            file_path,
+                            timeout.as_millis()
+                        ),
+                        Some(file_path),
+                    );
+                }
+                return Ok(FileScan::TimedOut);
+            }
+        },
+        None => try_process_kdl_content(&content, &transform),
+    };
+    // A rewrite that comes out byte-identical is cosmetic; writing it would only bump the mtime
+    let unchanged =
+        matches!(&result, Ok((modified, changes)) if changes.is_empty() || *modified == content);
+    let already_simplified = unchanged && has_editor_panes(&content, &options.transform);
+    timings.transform += transform_start.elapsed();
+
+    let (modified_content, mut changes) = result.map_err(|e| ScanError::Parse {
+        path: file_path.to_string(),
+        message: e.to_string(),
+    })?;
+
+    if unchanged {
+        return Ok(if already_simplified {
+            FileScan::AlreadySimplified
+        } else {
+            FileScan::NoEditorPanes
+        });
+    }
+
+    // Add file path to all changes from this file
+    for change in &mut changes {
+        change.file_path = file_path.to_string();
+    }
+
+    Ok(FileScan::Changed(FileChanges {
+        file_path: file_path.to_string(),
+        original_content: content,
+        modified_content,
+        changes,
+        mtime,
+        permissions,
+    }))
+}
+
+/// Runs [`try_process_kdl_content`] on a worker thread, giving up after `timeout`. Returns
+/// `None` on timeout; the worker is left to finish in the background and its result dropped.
+fn process_with_timeout(
+    content: &str,
+    transform: TransformOptions,
+    timeout: Duration,
+) -> Option<Result<(String, Vec<Changes>), KdlError>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let content = content.to_string();
+    std::thread::spawn(move || {
+        let _ = sender.send(try_process_kdl_content(&content, &transform));
+    });
+    receiver.recv_timeout(timeout).ok()
+}
+
+/// Whether `content` has any pane running an editor that gets simplified (and is selected).
+fn has_editor_panes(content: &str, transform: &TransformOptions) -> bool {
+    let Ok(doc) = content.parse::<KdlDocument>() else {
+        return false;
+    };
+    PaneWalker::with_options(&doc, transform.clone())
+        .filter_editor_panes()
+        .filter(|pane| transform.simplify_other_editors || pane.editor.as_deref() == Some("nvim"))
+        .any(|pane| editor_selected(transform, pane.editor.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::{TempDir, tempdir};
+
+    /// Reads and simplifies a single KDL file.
+    fn compute_file_changes(
+        file_path: &str,
+        options: &ScanOptions,
+        timings: &mut Timings,
+    ) -> Result<FileScan, ScanError> {
+        let read = read_layout_file(file_path, options, timings)?;
+        simplify_layout_file(file_path, read, options, timings)
+    }
+
+    /// Options that also simplify the vim pane in the editors fixture.
+    fn all_editors() -> ScanOptions {
+        ScanOptions {
+            transform: TransformOptions {
+                simplify_other_editors: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Creates a temp dir mimicking the zellij session structure with the mediactl fixture.
+    fn session_fixture() -> (TempDir, PathBuf) {
+        let temp = tempdir().unwrap();
+        let session_dir = temp.path().join("session_info/my_session");
+        fs::create_dir_all(&session_dir).unwrap();
+        let session_file = session_dir.join("session-layout.kdl");
+        fs::copy(
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+            &session_file,
+        )
+        .unwrap();
+        (temp, session_file)
+    }
+
+    #[test]
+    fn test_compute_file_changes_read_error() {
+        let temp = tempdir().unwrap();
+        let unreadable = temp.path().join("session-layout.kdl");
+        fs::create_dir(&unreadable).unwrap();
+        let unreadable = unreadable.to_str().unwrap();
+
+        let err =
+            compute_file_changes(unreadable, &ScanOptions::default(), &mut Timings::default())
+                .unwrap_err();
+
+        match err {
+            ScanError::Read { path, source } => {
+                assert_eq!(path, unreadable);
+                assert_ne!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            other => panic!("expected ScanError::Read, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_file_deleted_after_discovery_is_skipped() {
+        let (temp, session_file) = session_fixture();
+        let options = ScanOptions::default();
+        let mut discovered = Vec::new();
+        find_layout_files(temp.path(), &options, &mut discovered);
+        assert_eq!(discovered, vec![session_file.clone()]);
+
+        fs::remove_file(&session_file).unwrap();
+        let result = compute_file_changes(
+            discovered[0].to_str().unwrap(),
+            &options,
+            &mut Timings::default(),
+        );
+
+        assert!(matches!(result, Ok(FileScan::Vanished)), "{result:?}");
+    }
+
+    #[test]
+    fn test_compute_file_changes_invalid_utf8() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("session-layout.kdl");
+        let mut content =
+            b"pane command=\"/path/nvim\" {\n    args \"--cmd\" \"x=1\" \"caf".to_vec();
+        let valid_len = content.len();
+        content.extend([0xe9]); // latin-1 'é'
+        content.extend(b".txt\"\n}\n");
+        fs::write(&path, content).unwrap();
+        let path = path.to_str().unwrap();
+
+        match compute_file_changes(path, &ScanOptions::default(), &mut Timings::default())
+            .unwrap_err()
+        {
+            ScanError::Encoding {
+                path: err_path,
+                valid_up_to,
+            } => {
+                assert_eq!(err_path, path);
+                assert_eq!(valid_up_to, valid_len);
+            }
+            other => panic!("expected ScanError::Encoding, got {other:?}"),
+        }
+
+        let options = ScanOptions {
+            lossy: true,
+            ..Default::default()
+        };
+        let Ok(FileScan::Changed(file)) =
+            compute_file_changes(path, &options, &mut Timings::default())
+        else {
+            panic!("expected changes for {path}");
+        };
+        assert_eq!(file.changes[0].simplified_command, "nvim caf\u{FFFD}.txt");
+    }
+
+    #[test]
+    fn test_summary_json_file() {
+        let (temp, _) = session_fixture();
+        let json_path = temp.path().join("reports/nested/summary.json");
+        let options = ScanOptions {
+            quiet: true,
+            summary_json_file: Some(json_path.clone()),
+            ..Default::default()
+        };
+
+        let outcome = scan_layouts(temp.path().to_str().unwrap(), &options).unwrap();
+        assert!(outcome.errors.is_empty());
+
+        let report = json::parse(&fs::read_to_string(&json_path).unwrap()).unwrap();
+        let stats = report.get("stats").unwrap();
+        assert_eq!(stats.get("changes").and_then(Json::as_f64), Some(5.0));
+        assert_eq!(stats.get("files").and_then(Json::as_f64), Some(1.0));
+        assert_eq!(
+            report
+                .get("changes")
+                .and_then(Json::as_array)
+                .unwrap()
+                .len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_apply_skips_file_modified_during_run() {
+        let (temp, session_file) = session_fixture();
+        let original = fs::read_to_string(&session_file).unwrap();
+        let options = ScanOptions {
+            quiet: true,
+            ..Default::default()
+        };
+
+        let plan = compute_changes(temp.path(), &options);
+        assert_eq!(plan.files.len(), 1);
+
+        // Simulate zellij rewriting the file between our read and write
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(&session_file)
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let outcome = apply_changes(plan, &options);
+
+        assert_eq!(outcome.skipped, vec![session_file.to_str().unwrap()]);
+        assert!(outcome.changes.is_empty());
+        assert_eq!(fs::read_to_string(&session_file).unwrap(), original);
+    }
+
+    #[test]
+    fn test_apply_force_overwrites_file_modified_during_run() {
+        let (temp, session_file) = session_fixture();
+        let options = ScanOptions {
+            quiet: true,
+            force: true,
+            ..Default::default()
+        };
+
+        let plan = compute_changes(temp.path(), &options);
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(&session_file)
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let outcome = apply_changes(plan, &options);
+
+        assert!(outcome.skipped.is_empty());
+        assert_eq!(outcome.changes.len(), 5);
+        let expected =
+            fs::read_to_string("tests/fixtures/mediactl_expected/session-layout.kdl").unwrap();
+        assert_eq!(fs::read_to_string(&session_file).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_max_files_cap() {
+        let temp = tempdir().unwrap();
+        for session in ["one", "two", "three"] {
+            let session_dir = temp.path().join("session_info").join(session);
+            fs::create_dir_all(&session_dir).unwrap();
+            fs::copy(
+                "tests/fixtures/mediactl_input/session-layout.kdl",
+                session_dir.join("session-layout.kdl"),
+            )
+            .unwrap();
+        }
+        let dir = temp.path().to_str().unwrap();
+        let options = ScanOptions {
+            quiet: true,
+            dry_run: true,
+            max_files: Some(2),
+            ..Default::default()
+        };
+
+        let plan = compute_changes(temp.path(), &options);
+        assert!(plan.limit_reached);
+        assert_eq!(plan.discovered, 2);
+
+        match scan_layouts(dir, &options).unwrap_err() {
+            ScanError::TooManyFiles { path, limit } => {
+                assert_eq!(path, dir);
+                assert_eq!(limit, 2);
+            }
+            other => panic!("expected ScanError::TooManyFiles, got {other:?}"),
+        }
+
+        let warn = ScanOptions {
+            on_max_files: MaxFilesAction::Warn,
+            ..options
+        };
+        let outcome = scan_layouts(dir, &warn).unwrap();
+        assert_eq!(outcome.changes.len(), 10);
+
+        // Exactly at the cap is fine
+        let at_cap = ScanOptions {
+            max_files: Some(3),
+            ..warn
+        };
+        assert!(!compute_changes(temp.path(), &at_cap).limit_reached);
+    }
+    #[test]
+    fn test_editor_breakdown() {
+        let temp = tempdir().unwrap();
+        let session_dir = temp.path().join("session_info/editors");
+        fs::create_dir_all(&session_dir).unwrap();
+        fs::copy(
+            "tests/fixtures/editors_input/session-layout.kdl",
+            session_dir.join("session-layout.kdl"),
+        )
+        .unwrap();
+
+        let plan = compute_changes(temp.path(), &all_editors());
+        let changes: Vec<Changes> = plan.files.into_iter().flat_map(|f| f.changes).collect();
+
+        let no_aliases = BTreeMap::new();
+        assert_eq!(
+            editor_breakdown(&changes, &no_aliases).as_deref(),
+            Some("nvim: 2 panes, vim: 1 pane")
+        );
+        assert_eq!(editor_breakdown(&[], &no_aliases), None);
+
+        let aliases = BTreeMap::from([("nvim".to_string(), "Neovim".to_string())]);
+        assert_eq!(
+            editor_breakdown(&changes, &aliases).as_deref(),
+            Some("Neovim: 2 panes, vim: 1 pane")
+        );
+    }
+
+    #[test]
+    fn test_extension_breakdown() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("session-layout.kdl"),
+            r#"layout {
+    tab name="code" {
+        pane command="/nix/store/abc/bin/nvim" {
+            args "--cmd" "x=1" "src/a.rs" "src/b.rs" "README.md"
+        }
+        pane command="/usr/bin/vim" {
+            args "-u" "/nix/store/abc/vimrc" "-R" "notes.txt"
+        }
+        pane command="/nix/store/abc/bin/nvim" {
+            args "--cmd" "x=1" "+42" "CHANGELOG.md"
+        }
+        pane command="/nix/store/abc/bin/nvim" {
+            args "--cmd" "x=1" "."
+        }
+    }
+    tab name="dr project" {
+        pane command="/nix/store/abc/bin/nvim" {
+            args "--cmd" "x=1" "main.rs"
+        }
+    }
+}
+"#,
+        )
+        .unwrap();
+
+        let plan = compute_changes(temp.path(), &all_editors());
+        let changes: Vec<Changes> = plan.files.into_iter().flat_map(|f| f.changes).collect();
+
+        assert_eq!(changes.len(), 5);
+        assert_eq!(
+            extension_breakdown(&changes).as_deref(),
+            Some("rs: 3, md: 2, txt: 1")
+        );
+        assert_eq!(extension_breakdown(&[]), None);
+    }
+
+    #[test]
+    fn test_changes_csv() {
+        let changes = [Changes {
+            file_path: "/tmp/a/session-layout.kdl".to_string(),
+            original_command: "/usr/bin/nvim --cmd \"set a=1,2\" a.txt".to_string(),
+            simplified_command: "nvim a.txt".to_string(),
+            editor: Some("nvim".to_string()),
+            files: vec!["a.txt".to_string()],
+            pane: 1,
+        }];
+
+        let csv = changes_csv(&changes);
+
+        let expected = "\
+file_path,original_command,simplified_command,bytes_saved
+/tmp/a/session-layout.kdl,\"/usr/bin/nvim --cmd \"\"set a=1,2\"\" a.txt\",nvim a.txt,27
+";
+        assert_eq!(csv, expected);
+    }
+
+    #[test]
+    fn test_largest_changes() {
+        let temp = tempdir().unwrap();
+        for (session, fixture) in [
+            ("editors", "tests/fixtures/editors_input/session-layout.kdl"),
+            (
+                "mediactl",
+                "tests/fixtures/mediactl_input/session-layout.kdl",
+            ),
+        ] {
+            let session_dir = temp.path().join(session);
+            fs::create_dir_all(&session_dir).unwrap();
+            fs::copy(fixture, session_dir.join("session-layout.kdl")).unwrap();
+        }
+        let plan = compute_changes(temp.path(), &ScanOptions::default());
+        let changes: Vec<Changes> = plan.files.into_iter().flat_map(|f| f.changes).collect();
+        let longest = changes
+            .iter()
+            .map(|c| c.original_command.len())
+            .max()
+            .unwrap();
+
+        let top = largest_changes(&changes, 1);
+
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].original_command.len(), longest);
+        assert_eq!(largest_changes(&changes, 100).len(), changes.len());
+        assert!(
+            largest_changes(&changes, 3)
+                .windows(2)
+                .all(|w| w[0].original_command.len() >= w[1].original_command.len())
+        );
+    }
+
+    #[test]
+    fn test_session_breakdown() {
+        let temp = tempdir().unwrap();
+        for (session, fixture) in [
+            ("editors", "tests/fixtures/editors_input/session-layout.kdl"),
+            (
+                "mediactl",
+                "tests/fixtures/mediactl_input/session-layout.kdl",
+            ),
+        ] {
+            let session_dir = temp.path().join("session_info").join(session);
+            fs::create_dir_all(&session_dir).unwrap();
+            fs::copy(fixture, session_dir.join("session-layout.kdl")).unwrap();
+        }
+
+        let plan = compute_changes(temp.path(), &all_editors());
+        let changes: Vec<Changes> = plan.files.into_iter().flat_map(|f| f.changes).collect();
+
+        assert_eq!(
+            session_breakdown(&changes),
+            ["session editors: 3 changes", "session mediactl: 5 changes"]
+        );
+    }
+
+    #[test]
+    fn test_detailed_changes_preview_count() {
+        let changes: Vec<Changes> = (1..=5)
+            .map(|i| Changes {
+                file_path: format!("/s/{i}/session-layout.kdl"),
+                original_command: format!("/usr/bin/nvim --cmd x {i}.txt"),
+                simplified_command: format!("nvim {i}.txt"),
+                editor: Some("nvim".to_string()),
+                files: vec![format!("{i}.txt")],
+                pane: 1,
+            })
+            .collect();
+
+        assert_eq!(
+            detailed_changes(&changes, Some(2)),
+            [
+                "",
+                "1. /s/1/session-layout.kdl",
+                "   Original: /usr/bin/nvim --cmd x 1.txt",
+                "   Simplified: nvim 1.txt",
+                "",
+                "2. /s/2/session-layout.kdl",
+                "   Original: /usr/bin/nvim --cmd x 2.txt",
+                "   Simplified: nvim 2.txt",
+                "",
+                "... and 3 more",
+            ]
+        );
+        assert_eq!(detailed_changes(&changes, None).len(), 20);
+        assert_eq!(detailed_changes(&changes, Some(9)).len(), 20);
+    }
+
+    #[test]
+    fn test_change_tree() {
+        let change = |file: &str, pane: usize, original: &str| Changes {
+            file_path: file.to_string(),
+            original_command: original.to_string(),
+            simplified_command: "nvim a.txt".to_string(),
+            editor: Some("nvim".to_string()),
+            files: vec!["a.txt".to_string()],
+            pane,
+        };
+        let changes = [
+            change("/s/work/session-layout.kdl", 3, "/usr/bin/nvim a.txt"),
+            change("/s/home/session-layout.kdl", 1, "/bin/nvim a.txt"),
+            change("/s/work/session-layout.kdl", 1, "/opt/nvim a.txt"),
+            change("/s/work/session-layout.kdl", 3, "/usr/local/bin/nvim a.txt"),
+        ];
+
+        assert_eq!(
+            change_tree(&changes),
+            [
+                "session home",
+                "  /s/home/session-layout.kdl",
+                "    pane 1",
+                "      /bin/nvim a.txt -> nvim a.txt",
+                "session work",
+                "  /s/work/session-layout.kdl",
+                "    pane 1",
+                "      /opt/nvim a.txt -> nvim a.txt",
+                "    pane 3",
+                "      /usr/bin/nvim a.txt -> nvim a.txt",
+                "      /usr/local/bin/nvim a.txt -> nvim a.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_refuses_invalid_output() {
+        let (temp, session_file) = session_fixture();
+        let original = fs::read_to_string(&session_file).unwrap();
+        let options = ScanOptions {
+            quiet: true,
+            ..Default::default()
+        };
+
+        // Simulate a broken reconstruction of the layout
+        let mut plan = compute_changes(temp.path(), &options);
+        plan.files[0].modified_content = "layout {\n    pane command=\"nvim {\n".to_string();
+
+        let outcome = apply_changes(plan, &options);
+
+        assert!(outcome.changes.is_empty());
+        assert!(matches!(
+            outcome.errors.as_slice(),
+            [ScanError::InvalidOutput { .. }]
+        ));
+        assert_eq!(fs::read_to_string(&session_file).unwrap(), original);
+    }
+    #[test]
+    fn test_resolve_session_layout() {
+        let cache = tempdir().unwrap();
+        for version in ["0.40.1", "0.41.2"] {
+            for session in ["foo", "bar"] {
+                let session_dir = cache
+                    .path()
+                    .join(version)
+                    .join("session_info")
+                    .join(session);
+                fs::create_dir_all(&session_dir).unwrap();
+                fs::copy(
+                    "tests/fixtures/mediactl_input/session-layout.kdl",
+                    session_dir.join("session-layout.kdl"),
+                )
+                .unwrap();
+            }
+        }
+        let newest = cache
+            .path()
+            .join("0.40.1/session_info/foo/session-layout.kdl");
+        let file = fs::OpenOptions::new().write(true).open(&newest).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let layout = resolve_session_layout(cache.path(), "foo").unwrap();
+        assert_eq!(layout, newest);
+
+        let options = ScanOptions {
+            quiet: true,
+            ..Default::default()
+        };
+        let session_dir = layout.parent().unwrap().to_str().unwrap();
+        let outcome = scan_layouts(session_dir, &options).unwrap();
+        assert_eq!(outcome.changes.len(), 5);
+        assert!(
+            outcome
+                .changes
+                .iter()
+                .all(|c| c.file_path == newest.to_str().unwrap())
+        );
+
+        assert!(matches!(
+            resolve_session_layout(cache.path(), "missing"),
+            Err(ScanError::SessionNotFound { .. })
+        ));
+    }
+    #[test]
+    fn test_savings_by_file_heaviest_first() {
+        let temp = tempdir().unwrap();
+        for fixture in ["editors", "mediactl"] {
+            let session_dir = temp.path().join("session_info").join(fixture);
+            fs::create_dir_all(&session_dir).unwrap();
+            fs::copy(
+                format!("tests/fixtures/{fixture}_input/session-layout.kdl"),
+                session_dir.join("session-layout.kdl"),
+            )
+            .unwrap();
+        }
+        let options = ScanOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        let plan = compute_changes(temp.path(), &options);
+        let changes: Vec<Changes> = plan.files.into_iter().flat_map(|f| f.changes).collect();
+        let savings = savings_by_file(&changes);
+        dbg!(&savings);
+
+        assert_eq!(savings.len(), 2);
+        assert!(savings[0].0.ends_with("mediactl/session-layout.kdl"));
+        assert!(savings[0].1 > savings[1].1);
+    }
+    #[test]
+    fn test_patch_file_applies_to_copies() {
+        let (temp, session_file) = session_fixture();
+        let original = fs::read_to_string(&session_file).unwrap();
+        let out = tempdir().unwrap();
+        let patch_path = out.path().join("layouts.patch");
+        let options = ScanOptions {
+            quiet: true,
+            patch_file: Some(patch_path.clone()),
+            ..Default::default()
+        };
+
+        let outcome = scan_layouts(temp.path().to_str().unwrap(), &options).unwrap();
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.changes.len(), 5);
+        assert_eq!(fs::read_to_string(&session_file).unwrap(), original);
+
+        let patch = fs::read_to_string(&patch_path).unwrap();
+        assert!(patch.starts_with(
+            "--- a/session_info/my_session/session-layout.kdl\n+++ b/session_info/my_session/session-layout.kdl\n"
+        ));
+
+        // Apply the patch to a copy of the original tree
+        let copy = tempdir().unwrap();
+        let copy_file = copy
+            .path()
+            .join("session_info/my_session/session-layout.kdl");
+        fs::create_dir_all(copy_file.parent().unwrap()).unwrap();
+        fs::write(&copy_file, &original).unwrap();
+        let status = std::process::Command::new("git")
+            .arg("apply")
+            .arg(&patch_path)
+            .current_dir(copy.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let expected =
+            fs::read_to_string("tests/fixtures/mediactl_expected/session-layout.kdl").unwrap();
+        assert_eq!(fs::read_to_string(&copy_file).unwrap(), expected);
+    }
+    #[test]
+    fn test_no_recurse_processes_top_level_only() {
+        let temp = tempdir().unwrap();
+        let top_level = temp.path().join("session-layout.kdl");
+        let nested_dir = temp.path().join("nested");
+        fs::create_dir_all(&nested_dir).unwrap();
+        for file in [&top_level, &nested_dir.join("session-layout.kdl")] {
+            fs::copy("tests/fixtures/mediactl_input/session-layout.kdl", file).unwrap();
+        }
+        let options = ScanOptions {
+            dry_run: true,
+            no_recurse: true,
+            ..Default::default()
+        };
+
+        let plan = compute_changes(temp.path(), &options);
+
+        assert_eq!(plan.discovered, 1);
+        assert_eq!(plan.files.len(), 1);
+        assert_eq!(plan.files[0].file_path, top_level.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_already_simplified_count() {
+        let temp = tempdir().unwrap();
+        let fixtures = [
+            ("dirty", "tests/fixtures/mediactl_input/session-layout.kdl"),
+            (
+                "clean",
+                "tests/fixtures/mediactl_expected/session-layout.kdl",
+            ),
+            (
+                "clean2",
+                "tests/fixtures/editors_expected/session-layout.kdl",
+            ),
+        ];
+        for (dir, fixture) in fixtures {
+            let dir = temp.path().join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::copy(fixture, dir.join("session-layout.kdl")).unwrap();
+        }
+        let no_editors = temp.path().join("shell");
+        fs::create_dir_all(&no_editors).unwrap();
+        fs::write(
+            no_editors.join("session-layout.kdl"),
+            "layout {\n    tab name=\"shell\" {\n        pane command=\"htop\"\n    }\n}\n",
+        )
+        .unwrap();
+        let options = ScanOptions {
+            dry_run: true,
+            quiet: true,
+            ..Default::default()
+        };
+
+        let outcome = scan_layouts(temp.path().to_str().unwrap(), &options).unwrap();
+
+        assert!(outcome.errors.is_empty());
+        assert_eq!(changed_files(&outcome).len(), 1);
+        assert_eq!(outcome.already_simplified, 2);
+    }
+
+    #[test]
+    fn test_scan_zip_reports_without_writing() {
+        let temp = tempdir().unwrap();
+        let zip_path = temp.path().join("sessions.zip");
+        fs::copy("tests/fixtures/sessions.zip", &zip_path).unwrap();
+        let original = fs::read(&zip_path).unwrap();
+        let options = ScanOptions {
+            quiet: true,
+            ..Default::default()
+        };
+
+        let outcome = scan_zip(&zip_path, false, &options).unwrap();
+
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.changes.len(), 5);
+        assert_eq!(
+            changed_files(&outcome),
+            [format!(
+                "{}/session_info/mediactl/session-layout.kdl",
+                zip_path.display()
+            )]
+        );
+        assert_eq!(fs::read(&zip_path).unwrap(), original);
+
+        // Rewriting leaves nothing to change on the next audit
+        let outcome = scan_zip(&zip_path, true, &options).unwrap();
+        assert_eq!(outcome.changes.len(), 5);
+        let outcome = scan_zip(&zip_path, false, &options).unwrap();
+        assert!(outcome.changes.is_empty());
+        assert_eq!(outcome.already_simplified, 1);
+    }
+
+    #[test]
+    fn test_traversal_order_is_sorted() {
+        let temp = tempdir().unwrap();
+        let sessions = ["b", "d", "a/z", "c", "a"];
+        for session in sessions {
+            let dir = temp.path().join(session);
+            fs::create_dir_all(&dir).unwrap();
+            fs::copy(
+                "tests/fixtures/mediactl_input/session-layout.kdl",
+                dir.join("session-layout.kdl"),
+            )
+            .unwrap();
+        }
+
+        let plan = compute_changes(temp.path(), &ScanOptions::default());
+
+        let order: Vec<&str> = plan
+            .files
+            .iter()
+            .map(|f| {
+                Path::new(&f.file_path)
+                    .parent()
+                    .unwrap()
+                    .strip_prefix(temp.path())
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(order, ["a", "a/z", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_output_dir_conflict_policies() {
+        let (temp, session_file) = session_fixture();
+        let original = fs::read_to_string(&session_file).unwrap();
+        let expected =
+            fs::read_to_string("tests/fixtures/mediactl_expected/session-layout.kdl").unwrap();
+        let output_dir = tempdir().unwrap();
+        let output = output_dir
+            .path()
+            .join(session_file.strip_prefix(temp.path()).unwrap());
+        fs::create_dir_all(output.parent().unwrap()).unwrap();
+        let scan = |policy| {
+            let options = ScanOptions {
+                quiet: true,
+                output_dir: Some(output_dir.path().to_path_buf()),
+                on_conflict: policy,
+                ..Default::default()
+            };
+            scan_layouts(temp.path().to_str().unwrap(), &options)
+        };
+
+        fs::write(&output, "old").unwrap();
+        let outcome = scan(ConflictPolicy::Skip).unwrap();
+        assert!(outcome.changes.is_empty());
+        assert_eq!(fs::read_to_string(&output).unwrap(), "old");
+
+        assert!(matches!(
+            scan(ConflictPolicy::Error),
+            Err(ScanError::OutputExists { .. })
+        ));
+        assert_eq!(fs::read_to_string(&output).unwrap(), "old");
+
+        let outcome = scan(ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(outcome.changes.len(), 5);
+        assert_eq!(fs::read_to_string(&output).unwrap(), expected);
+
+        // The scanned layout itself is never touched
+        assert_eq!(fs::read_to_string(&session_file).unwrap(), original);
+    }
+
+    #[test]
+    fn test_sidecar_report() {
+        let (temp, session_file) = session_fixture();
+        let sidecar = session_file.with_file_name("session-layout.kdl.zch-report.json");
+        let scan = |dry_run| {
+            let options = ScanOptions {
+                quiet: true,
+                dry_run,
+                sidecar_report: true,
+                ..Default::default()
+            };
+            scan_layouts(temp.path().to_str().unwrap(), &options).unwrap()
+        };
+
+        scan(true);
+        assert!(!sidecar.exists());
+
+        let outcome = scan(false);
+        let report = json::parse(&fs::read_to_string(&sidecar).unwrap()).unwrap();
+        assert_eq!(
+            report.get("file").and_then(Json::as_str),
+            session_file.to_str()
+        );
+        assert!(report.get("timestamp").and_then(Json::as_str).is_some());
+        let changes = report.get("changes").and_then(Json::as_array).unwrap();
+        assert_eq!(changes.len(), 5);
+        assert_eq!(changes.len(), outcome.changes.len());
+        for (change, expected) in changes.iter().zip(&outcome.changes) {
+            assert_eq!(
+                change.get("original_command").and_then(Json::as_str),
+                Some(expected.original_command.as_str())
+            );
+            assert_eq!(
+                change.get("simplified_command").and_then(Json::as_str),
+                Some(expected.simplified_command.as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn test_keep_backups_rotation() {
+        let temp = tempdir().unwrap();
+        let session_file = temp.path().join("session-layout.kdl");
+        let backup = |suffix: &str| {
+            temp.path()
+                .join(format!("session-layout.kdl.bak{}", suffix))
+        };
+        let layout = |run: usize| {
+            format!(
+                "layout {{\n    pane command=\"/nix/store/abc/bin/nvim\" {{\n        args \"--cmd\" \"x=1\" \"run{}.txt\"\n    }}\n}}\n",
+                run
+            )
+        };
+        let options = ScanOptions {
+            quiet: true,
+            keep_backups: 2,
+            ..Default::default()
+        };
+
+        for run in 1..=3 {
+            fs::write(&session_file, layout(run)).unwrap();
+            let outcome = scan_layouts(temp.path().to_str().unwrap(), &options).unwrap();
+            assert!(outcome.errors.is_empty(), "{:?}", outcome.errors);
+            assert_eq!(outcome.changes.len(), 1);
+        }
+
+        assert_eq!(fs::read_to_string(backup("")).unwrap(), layout(3));
+        assert_eq!(fs::read_to_string(backup(".1")).unwrap(), layout(2));
+        assert!(!backup(".2").exists());
+        assert!(
+            fs::read_to_string(&session_file)
+                .unwrap()
+                .contains("command=\"nvim\"")
+        );
+
+        // No backups are written by default
+        let (temp, session_file) = session_fixture();
+        scan_layouts(
+            temp.path().to_str().unwrap(),
+            &ScanOptions {
+                quiet: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(
+            !session_file
+                .with_file_name("session-layout.kdl.bak")
+                .exists()
+        );
+    }
+
+    #[test]
+    fn test_file_timeout_skips_slow_file() {
+        let temp = tempdir().unwrap();
+        let slow = temp.path().join("slow/session-layout.kdl");
+        let fast = temp.path().join("fast/session-layout.kdl");
+        fs::create_dir_all(slow.parent().unwrap()).unwrap();
+        fs::create_dir_all(fast.parent().unwrap()).unwrap();
+        let pane = "    pane command=\"/nix/store/abc/bin/nvim\" {\n        args \"--cmd\" \"x=1\" \"a.txt\"\n    }\n";
+        fs::write(&slow, format!("layout {{\n{}}}\n", pane.repeat(50_000))).unwrap();
+        fs::write(&fast, format!("layout {{\n{}}}\n", pane)).unwrap();
+        let original = fs::read_to_string(&slow).unwrap();
+
+        let options = ScanOptions {
+            quiet: true,
+            file_timeout: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let plan = compute_changes(slow.parent().unwrap(), &options);
+        assert_eq!(plan.timed_out, vec![slow.to_str().unwrap()]);
+        assert!(plan.files.is_empty());
+        assert!(plan.errors.is_empty());
+
+        let options = ScanOptions {
+            file_timeout: Some(Duration::from_secs(30)),
+            ..options
+        };
+        let outcome = scan_layouts(fast.parent().unwrap().to_str().unwrap(), &options).unwrap();
+        assert!(outcome.timed_out.is_empty());
+        assert_eq!(outcome.changes.len(), 1);
+        assert_eq!(fs::read_to_string(&slow).unwrap(), original);
+    }
+
+    #[test]
+    fn test_clean_artifacts() {
+        let temp = tempdir().unwrap();
+        let session_dir = temp.path().join("session_info/my_session");
+        fs::create_dir_all(&session_dir).unwrap();
+        let artifacts = [
+            "session_info/my_session/session-layout.kdl.bak",
+            "session_info/my_session/session-layout.kdl.bak.1",
+            "session_info/my_session/session-layout.kdl.bak.12",
+            "session_info/my_session/session-layout.kdl.zch-report.json",
+            "layout.kdl.bak",
+        ];
+        let kept = [
+            "session_info/my_session/session-layout.kdl",
+            "session_info/my_session/notes.bak",
+            "session_info/my_session/session-layout.kdl.bak.old",
+            "session_info/my_session/.kdl.bak",
+            "report.zch-report.json",
+            "config.kdl",
+        ];
+        for name in artifacts.iter().chain(&kept) {
+            fs::write(temp.path().join(name), "x").unwrap();
+        }
+        let mut expected: Vec<PathBuf> = artifacts.iter().map(|n| temp.path().join(n)).collect();
+        expected.sort();
+
+        let outcome = clean_artifacts(temp.path(), true).unwrap();
+        assert_eq!(outcome.removed, expected);
+        for name in artifacts.iter().chain(&kept) {
+            assert!(temp.path().join(name).exists(), "{name}");
+        }
+
+        let outcome = clean_artifacts(temp.path(), false).unwrap();
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.removed, expected);
+        for name in artifacts {
+            assert!(!temp.path().join(name).exists(), "{name}");
+        }
+        for name in kept {
+            assert!(temp.path().join(name).exists(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_identical_rewrite_not_written() {
+        let (temp, session_file) = session_fixture();
+        let options = ScanOptions {
+            quiet: true,
+            keep_backups: 1,
+            ..Default::default()
+        };
+        let mtime = fs::metadata(&session_file).unwrap().modified().unwrap();
+
+        // A plan whose only file has changes reported but comes out byte-identical
+        let mut plan = compute_changes(temp.path(), &options);
+        assert_eq!(plan.files.len(), 1);
+        assert!(!plan.files[0].changes.is_empty());
+        plan.files[0].modified_content = plan.files[0].original_content.clone();
+        std::thread::sleep(Duration::from_millis(10));
+
+        let outcome = apply_changes(plan, &options);
+        assert!(outcome.changes.is_empty());
+        assert!(outcome.errors.is_empty());
+        assert!(changed_files(&outcome).is_empty());
+        assert_eq!(
+            fs::metadata(&session_file).unwrap().modified().unwrap(),
+            mtime
+        );
+        assert!(
+            !session_file
+                .with_file_name("session-layout.kdl.bak")
+                .exists()
+        );
+    }
+
+    #[test]
+    fn test_find_empty_sessions() {
+        let (temp, _) = session_fixture();
+        let stub_dir = temp.path().join("session_info/stub");
+        let blank_dir = temp.path().join("session_info/blank");
+        fs::create_dir_all(&stub_dir).unwrap();
+        fs::create_dir_all(&blank_dir).unwrap();
+        let stub = stub_dir.join("session-layout.kdl");
+        let blank = blank_dir.join("session-layout.kdl");
+        fs::write(&stub, "layout {\n    tab name=\"Tab #1\"\n}\n").unwrap();
+        fs::write(&blank, "").unwrap();
+
+        let report =
+            find_empty_sessions(temp.path().to_str().unwrap(), &ScanOptions::default()).unwrap();
+
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            report.found,
+            vec![
+                EmptySession {
+                    file_path: blank.display().to_string()
+                },
+                EmptySession {
+                    file_path: stub.display().to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            report.found[1].to_string(),
+            format!("{}: no panes", stub.display())
+        );
+    }
+
+    #[test]
+    fn test_compute_changes_with_callback() {
+        let (temp, _) = session_fixture();
+        let second = temp.path().join("session_info/other/session-layout.kdl");
+        fs::create_dir_all(second.parent().unwrap()).unwrap();
+        fs::copy("tests/fixtures/editors_input/session-layout.kdl", &second).unwrap();
+
+        let mut seen = Vec::new();
+        let plan = compute_changes_with(temp.path(), &ScanOptions::default(), &mut |change| {
+            seen.push(change.clone())
+        });
+
+        let planned: Vec<&Changes> = plan.files.iter().flat_map(|f| &f.changes).collect();
+        assert_eq!(plan.files.len(), 2);
+        assert_eq!(seen.len(), planned.len());
+        for (seen, planned) in seen.iter().zip(planned) {
+            assert_eq!(seen.file_path, planned.file_path);
+            assert_eq!(seen.original_command, planned.original_command);
+            assert_eq!(seen.simplified_command, planned.simplified_command);
+            assert!(!seen.file_path.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_batch_matches_per_file_reads() {
+        let temp = tempdir().unwrap();
+        let fixtures = [
+            "tests/fixtures/editors_input/session-layout.kdl",
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+            "tests/fixtures/mediactl_expected/session-layout.kdl",
+            "tests/fixtures/editors_expected/session-layout.kdl",
+        ];
+        for i in 0..8 {
+            let session_dir = temp.path().join(format!("session_info/s{i}"));
+            fs::create_dir_all(&session_dir).unwrap();
+            fs::copy(fixtures[i % 4], session_dir.join("session-layout.kdl")).unwrap();
+        }
+
+        let summarize = |read_batch: usize| {
+            let options = ScanOptions::builder().read_batch(read_batch).build();
+            let plan = compute_changes(temp.path(), &options);
+            let files: Vec<(String, String, usize)> = plan
+                .files
+                .into_iter()
+                .map(|file| (file.file_path, file.modified_content, file.changes.len()))
+                .collect();
+            (files, plan.already_simplified, plan.errors.len())
+        };
+
+        let per_file = summarize(1);
+        assert_eq!(per_file.0.len(), 4);
+        assert_eq!(summarize(4), per_file);
+        assert_eq!(summarize(3), per_file);
+    }
+
+    #[test]
+    fn test_by_content_discovery() {
+        let temp = tempdir().unwrap();
+        fs::copy(
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+            temp.path().join("layout.kdl"),
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join("config.kdl"),
+            "editor \"nvim\"\nmax_command_len 4096\n",
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join("notes.txt"),
+            "pane command=\"/nix/store/abc/bin/nvim\" { args \"--cmd\" \"x=1\" \"a.txt\"; }\n",
+        )
+        .unwrap();
+        let scan = |by_content| {
+            let options = ScanOptions {
+                dry_run: true,
+                quiet: true,
+                by_content,
+                ..Default::default()
+            };
+            scan_layouts(temp.path().to_str().unwrap(), &options).unwrap()
+        };
+
+        assert!(scan(false).changes.is_empty());
+
+        let outcome = scan(true);
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.changes.len(), 5);
+        for change in &outcome.changes {
+            assert!(
+                change.file_path.ends_with("layout.kdl"),
+                "{}",
+                change.file_path
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_delay_paces_writes() {
+        let temp = tempdir().unwrap();
+        for session in ["a", "b", "c"] {
+            let dir = temp.path().join(session);
+            fs::create_dir_all(&dir).unwrap();
+            fs::copy(
+                "tests/fixtures/mediactl_input/session-layout.kdl",
+                dir.join("session-layout.kdl"),
+            )
+            .unwrap();
+        }
+        let options = ScanOptions {
+            quiet: true,
+            write_delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let outcome = scan_layouts(temp.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(outcome.changes.len(), 15);
+        // Three writes, so two delays between them
+        assert!(
+            start.elapsed() >= Duration::from_millis(100),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn test_review_applies_only_accepted_files() {
+        let temp = tempdir().unwrap();
+        for dir in ["one", "two"] {
+            let dir = temp.path().join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::copy(
+                "tests/fixtures/mediactl_input/session-layout.kdl",
+                dir.join("session-layout.kdl"),
+            )
+            .unwrap();
+        }
+        let original =
+            fs::read_to_string("tests/fixtures/mediactl_input/session-layout.kdl").unwrap();
+        let options = ScanOptions {
+            quiet: true,
+            ..Default::default()
+        };
+        let plan = compute_changes(temp.path(), &options);
+        assert_eq!(plan.files.len(), 2);
+        let (first, second) = (plan.files[0].clone(), plan.files[1].clone());
+
+        let mut input = std::io::Cursor::new("a\ns\n");
+        let mut output = Vec::new();
+        let plan = review_changes(plan, temp.path(), &mut input, &mut output).unwrap();
+        let outcome = apply_changes(plan, &options);
+
+        let prompts = String::from_utf8(output).unwrap();
+        assert_eq!(prompts.matches("[a]pply / [s]kip / [q]uit").count(), 2);
+        assert!(prompts.contains("--- a/"));
+        assert!(outcome.errors.is_empty());
+        assert_eq!(
+            fs::read_to_string(&first.file_path).unwrap(),
+            first.modified_content
+        );
+        assert_eq!(fs::read_to_string(&second.file_path).unwrap(), original);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (temp, session_file) = session_fixture();
+        fs::set_permissions(&session_file, fs::Permissions::from_mode(0o600)).unwrap();
+        let options = ScanOptions {
+            quiet: true,
+            ..Default::default()
+        };
+
+        let outcome = scan_layouts(temp.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(outcome.changes.len(), 5);
+        let mode = fs::metadata(&session_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // No temp files left behind
+        assert_eq!(
+            fs::read_dir(session_file.parent().unwrap())
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+}
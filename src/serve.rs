@@ -0,0 +1,88 @@
+//! Newline-delimited JSON protocol for calling the tool from a long-lived process, such as a
+//! zellij plugin.
+//!
+//! Each request is one line: `{"op":"format","command":"..."}` or
+//! `{"op":"process_kdl","content":"..."}`. Each gets one response line: `{"result":"..."}` or
+//! `{"error":"..."}`.
+
+use crate::json::{self, Json};
+use crate::kdl::{TransformOptions, try_process_kdl_content};
+use crate::nvim::format_nvim;
+use std::io::{BufRead, Write};
+
+/// Answers requests from `input` until EOF. Blank lines are ignored; malformed requests get an
+/// error response and don't end the loop.
+pub fn serve(
+    input: impl BufRead,
+    mut output: impl Write,
+    options: &TransformOptions,
+) -> std::io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match handle_request(&line, options) {
+            Ok(result) => Json::object([("result", Json::from(result))]),
+            Err(message) => Json::object([("error", Json::from(message))]),
+        };
+        writeln!(output, "{}", response)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Dispatches a single request line to the matching operation.
+fn handle_request(line: &str, options: &TransformOptions) -> Result<String, String> {
+    let request = json::parse(line).map_err(|e| format!("invalid request: {}", e))?;
+    let string_field = |name: &str| {
+        request
+            .get(name)
+            .and_then(Json::as_str)
+            .ok_or_else(|| format!("missing string field {:?}", name))
+    };
+
+    match string_field("op")? {
+        "format" => Ok(format_nvim(string_field("command")?)),
+        "process_kdl" => try_process_kdl_content(string_field("content")?, options)
+            .map(|(modified, _)| modified)
+            .map_err(|e| e.to_string()),
+        other => Err(format!("unknown op {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_serve_requests() {
+        let input = concat!(
+            r#"{"op":"format","command":"/nix/store/abc/bin/nvim --cmd x=1 a.txt"}"#,
+            "\n",
+            r#"{"op":"process_kdl","content":"pane command=\"/usr/bin/nvim\" {\n    args \"--cmd\" \"x=1\" \"b.txt\"\n}\n"}"#,
+            "\n\n",
+            r#"{"op":"reverse"}"#,
+            "\n",
+            "not json\n",
+        );
+        let mut output = Vec::new();
+
+        serve(input.as_bytes(), &mut output, &TransformOptions::default()).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let responses: Vec<Json> = output.lines().map(|l| json::parse(l).unwrap()).collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(
+            responses[0].get("result").and_then(Json::as_str),
+            Some("nvim a.txt")
+        );
+        assert_eq!(
+            responses[1].get("result").and_then(Json::as_str),
+            Some("pane command=\"nvim\" {\n    args \"b.txt\"\n}\n")
+        );
+        assert!(responses[2].get("error").is_some());
+        assert!(responses[3].get("error").is_some());
+    }
+}
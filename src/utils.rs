@@ -1,27 +1,466 @@
-use std::io::Write;
+use crate::json::Json;
 use chrono::Local;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 /// Expands ~ in paths to the actual home directory.
 pub fn expand_home(path: &str) -> String {
     if path.starts_with("~/")
-        && let Ok(home) = std::env::var("HOME") {
-            return format!("{}{}", home, &path[1..]);
-        }
+        && let Ok(home) = std::env::var("HOME")
+    {
+        return format!("{}{}", home, &path[1..]);
+    }
     path.to_string()
 }
 
-/// Logs a command transformation to the log file.
-pub fn log_command(command: &str, formatted: &str) {
-    let log_path = "/tmp/nvim-resurrect.log";
-    let mut log_file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(log_path)
+/// Zellij's cache directory: `$XDG_CACHE_HOME/zellij`, falling back to `~/.cache/zellij`.
+pub fn zellij_cache_dir() -> PathBuf {
+    match std::env::var("XDG_CACHE_HOME") {
+        Ok(cache) if !cache.is_empty() => PathBuf::from(cache).join("zellij"),
+        _ => PathBuf::from(expand_home("~/.cache/zellij")),
+    }
+}
+
+/// Looks `name` up in the directories on `$PATH`, like `which`. Returns the first executable
+/// file found.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || path.with_extension("exe").is_file()
+}
+
+/// Default log file shared by the hook and `scan-layouts`.
+pub const LOG_PATH: &str = "/tmp/nvim-resurrect.log";
+
+/// Lock file `scan-layouts` holds, next to the log file, while it runs.
+pub const LOCK_FILE: &str = "zch.lock";
+
+/// An advisory lock on a lock file, released when dropped (or when the process exits). Keeps
+/// two runs over the same tree from racing on writes.
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Takes the lock on `path` without waiting, creating the file and its parent directories
+    /// if needed. Fails with [`std::io::ErrorKind::WouldBlock`] if another run holds it.
+    pub fn acquire(path: impl AsRef<Path>) -> std::io::Result<RunLock> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(RunLock { _file: file }),
+            Err(std::fs::TryLockError::WouldBlock) => Err(std::io::Error::new(
+                std::io::ErrorKind::WouldBlock,
+                format!(
+                    "another run is in progress (lock held on {}; use --no-lock to run anyway)",
+                    path.display()
+                ),
+            )),
+            Err(std::fs::TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// Appends entries to a log file. Each entry is written with a single call while holding a
+/// lock, so entries logged from several threads never interleave. Writers within a process
+/// share one logger per path through [`Logger::shared`].
+pub struct Logger {
+    file: Mutex<File>,
+}
+
+impl Logger {
+    /// Opens `path` for appending, creating it and its parent directories if needed.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Logger> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("could not create log directory {}: {}", parent.display(), e),
+                )
+            })?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("could not open log file {}: {}", path.display(), e),
+                )
+            })?;
+        Ok(Logger {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// The process-wide logger for `path`, opened on first use. Every call with the same path
+    /// returns the same logger, so all writers in the process are serialized by its lock.
+    pub fn shared(path: impl AsRef<Path>) -> std::io::Result<&'static Logger> {
+        static LOGGERS: OnceLock<Mutex<HashMap<PathBuf, &'static Logger>>> = OnceLock::new();
+        let path = path.as_ref();
+        let mut loggers = LOGGERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(logger) = loggers.get(path) {
+            return Ok(logger);
+        }
+        let logger: &'static Logger = Box::leak(Box::new(Logger::open(path)?));
+        loggers.insert(path.to_path_buf(), logger);
+        Ok(logger)
+    }
+
+    /// Writes `entry` as a whole.
+    pub fn log(&self, entry: &str) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(entry.as_bytes())
+    }
+}
+
+/// Identifies the writer of a log entry on a shared machine, as `host pid 1234`.
+pub fn log_identity() -> String {
+    let host = std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("{} pid {}", host, std::process::id())
+}
+
+/// Logs a command transformation to `log_file`.
+pub fn log_command(log_file: &Path, command: &str, formatted: &str) -> std::io::Result<()> {
+    Logger::shared(log_file)?.log(&command_log_entry(command, formatted))
+}
+
+/// Logs a warning to `log_file`.
+pub fn log_warning(log_file: &Path, message: &str) {
+    if let Ok(logger) = Logger::shared(log_file) {
+        logger.log(&warning_log_entry(message)).ok();
+    }
+}
+
+fn warning_log_entry(message: &str) -> String {
+    log_entry([("warning", Json::from(message))])
+}
+
+fn command_log_entry(command: &str, formatted: &str) -> String {
+    log_entry([
+        ("original_command", Json::from(command)),
+        ("formatted_command", Json::from(formatted)),
+    ])
+}
+
+/// The log entry `scan-layouts` writes after rewriting `count` files.
+pub fn processed_log_entry(count: usize) -> String {
+    log_entry([("processed_files", Json::from(count))])
+}
+
+/// A JSON line with the current timestamp and [`log_identity`], followed by `fields`. This is
+/// the schema [`migrate_log`] converts the older text entries to.
+fn log_entry<const N: usize>(fields: [(&str, Json); N]) -> String {
+    let timestamp = Local::now().format("%Y-%m-%d %I:%M:%S %p").to_string();
+    let entry = Json::object(
+        [
+            ("timestamp", Json::from(timestamp)),
+            ("host", Json::from(log_identity())),
+        ]
+        .into_iter()
+        .chain(fields),
+    );
+    format!("{}\n", entry)
+}
+
+/// Rewrites a log in the text format written by older versions of [`log_command`],
+/// [`log_warning`] and `scan-layouts` as JSON lines, one object per entry. Older entries
+/// without a host are understood too, and entries that already are JSON lines are kept. Other
+/// lines that aren't part of a recognized entry are kept as they are, and `warn` is called for
+/// each such entry.
+pub fn migrate_log(content: &str, mut warn: impl FnMut(&str)) -> String {
+    let mut migrated = String::new();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('{') && crate::json::parse(line).is_ok() {
+            migrated.push_str(line);
+            migrated.push('\n');
+            continue;
+        }
+        let mut raw = vec![line];
+        let entry = if line == "---" {
+            while let Some(next) = lines.next_if(|l| !l.is_empty()) {
+                raw.push(next);
+            }
+            command_entry_json(&raw[1..])
+        } else {
+            bracketed_entry_json(line)
+        };
+        match entry {
+            Some(json) => {
+                migrated.push_str(&json.to_string());
+                migrated.push('\n');
+            }
+            None => {
+                warn(&format!(
+                    "keeping unrecognized log entry: {}",
+                    raw.join(" ")
+                ));
+                for line in raw {
+                    migrated.push_str(line);
+                    migrated.push('\n');
+                }
+            }
+        }
+    }
+    migrated
+}
+
+/// Parses the `Key: value` lines of a `---` command entry.
+fn command_entry_json(lines: &[&str]) -> Option<Json> {
+    let mut fields = Vec::new();
+    for line in lines {
+        let (key, value) = line.split_once(": ")?;
+        let key = match key {
+            "Timestamp" => "timestamp",
+            "Host" => "host",
+            "Original command" => "original_command",
+            "Formatted command" => "formatted_command",
+            _ => return None,
+        };
+        fields.push((key, Json::from(value)));
+    }
+    let has = |name: &str| fields.iter().any(|(key, _)| *key == name);
+    if !has("original_command") || !has("formatted_command") {
+        return None;
+    }
+    Some(Json::object(fields))
+}
+
+/// Parses a one-line `[timestamp] [host] message` entry, where `[host]` is optional.
+fn bracketed_entry_json(line: &str) -> Option<Json> {
+    let (timestamp, rest) = line.strip_prefix('[')?.split_once("] ")?;
+    let mut fields = vec![("timestamp", Json::from(timestamp))];
+    let message = match rest.strip_prefix('[').and_then(|r| r.split_once("] ")) {
+        Some((host, message)) => {
+            fields.push(("host", Json::from(host)));
+            message
+        }
+        None => rest,
+    };
+
+    if let Some(warning) = message.strip_prefix("Warning: ") {
+        fields.push(("warning", Json::from(warning)));
+    } else {
+        let count = message
+            .strip_prefix("Processed ")?
+            .strip_suffix(" files")?
+            .parse::<usize>()
+            .ok()?;
+        fields.push(("processed_files", Json::from(count)));
+    }
+    Some(Json::object(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_run_lock_held_by_another_run() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("logs").join(LOCK_FILE);
+
+        let held = RunLock::acquire(&path).unwrap();
+        let second = std::thread::spawn({
+            let path = path.clone();
+            move || RunLock::acquire(&path).map(|_| ())
+        })
+        .join()
         .unwrap();
+        let err = second.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        assert!(
+            err.to_string().contains("another run is in progress"),
+            "{err}"
+        );
 
-    let timestamp = Local::now().format("%Y-%m-%d %I:%M:%S %p");
-    writeln!(log_file, "\n---\nTimestamp: {}", timestamp).ok();
-    writeln!(log_file, "Original command: {}", command).ok();
-    writeln!(log_file, "Formatted command: {}", formatted).ok();
-    writeln!(log_file).ok();
+        drop(held);
+        assert!(RunLock::acquire(&path).is_ok());
+    }
+
+    #[test]
+    fn test_migrate_log() {
+        let old = "
+---
+Timestamp: 2025-01-02 03:04:05 PM
+Original command: /nix/store/abc/bin/nvim --cmd x=1 a.txt
+Formatted command: nvim a.txt
+
+
+---
+Timestamp: 2025-01-02 03:05:00 PM
+Host: box pid 42
+Original command: nvim b.txt
+Formatted command: nvim b.txt
+
+
+[2025-01-02 03:06:00 PM] Processed 3 files
+[2025-01-02 03:07:00 PM] [box pid 43] Warning: command is too long
+garbage line
+";
+        let mut warnings = Vec::new();
+
+        let migrated = migrate_log(old, |w| warnings.push(w.to_string()));
+
+        let lines: Vec<&str> = migrated.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[4], "garbage line");
+        let entries: Vec<Json> = lines[..4].iter().map(|l| json::parse(l).unwrap()).collect();
+        assert_eq!(
+            entries[0].get("formatted_command").and_then(Json::as_str),
+            Some("nvim a.txt")
+        );
+        assert!(entries[0].get("host").is_none());
+        assert_eq!(
+            entries[1].get("host").and_then(Json::as_str),
+            Some("box pid 42")
+        );
+        assert_eq!(
+            entries[2].get("processed_files").and_then(Json::as_f64),
+            Some(3.0)
+        );
+        assert_eq!(
+            entries[3].get("warning").and_then(Json::as_str),
+            Some("command is too long")
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("garbage line"));
+    }
+
+    #[test]
+    fn test_command_log_entry_has_pid() {
+        let entry = command_log_entry("nvim --cmd x a.txt", "nvim a.txt");
+        let entry = json::parse(entry.trim_end()).unwrap();
+        assert!(
+            entry
+                .get("host")
+                .and_then(Json::as_str)
+                .is_some_and(|host| host.ends_with(&format!("pid {}", std::process::id())))
+        );
+        assert_eq!(
+            entry.get("original_command").and_then(Json::as_str),
+            Some("nvim --cmd x a.txt")
+        );
+        assert_eq!(
+            entry.get("formatted_command").and_then(Json::as_str),
+            Some("nvim a.txt")
+        );
+    }
+
+    #[test]
+    fn test_written_entries_match_migrated_schema() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("test.log");
+        std::fs::write(
+            &path,
+            "\n---\nTimestamp: 2025-01-02 03:04:05 PM\nOriginal command: nvim b.txt\nFormatted command: nvim b.txt\n\n",
+        )
+        .unwrap();
+        log_command(&path, "nvim --cmd x a.txt", "nvim a.txt").unwrap();
+        log_warning(&path, "command is too long");
+
+        let mut warnings = Vec::new();
+        let migrated = migrate_log(&std::fs::read_to_string(&path).unwrap(), |w| {
+            warnings.push(w.to_string())
+        });
+
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let entries: Vec<Json> = migrated.lines().map(|l| json::parse(l).unwrap()).collect();
+        assert_eq!(entries.len(), 3);
+        let keys = |entry: &Json| match entry {
+            Json::Object(fields) => fields.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        assert_eq!(
+            keys(&entries[1]),
+            ["timestamp", "host", "original_command", "formatted_command"]
+        );
+        assert_eq!(
+            entries[1].get("formatted_command").and_then(Json::as_str),
+            Some("nvim a.txt")
+        );
+        assert_eq!(keys(&entries[2]), ["timestamp", "host", "warning"]);
+        // Already migrated logs are left as they are
+        assert_eq!(migrate_log(&migrated, |_| {}), migrated);
+    }
+
+    #[test]
+    fn test_logger_concurrent_entries_intact() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("test.log");
+
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let logger = Logger::shared(&path).unwrap();
+                    for idx in 0..100 {
+                        let line = format!("thread {} entry {} {}\n", thread, idx, "x".repeat(512));
+                        logger.log(&line).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(std::ptr::eq(
+            Logger::shared(&path).unwrap(),
+            Logger::shared(&path).unwrap()
+        ));
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 800);
+        for line in lines {
+            let mut parts = line.split(' ');
+            assert_eq!(parts.next(), Some("thread"), "Mangled line: {line}");
+            assert!(
+                parts.nth(3).is_some_and(|x| x == "x".repeat(512)),
+                "Mangled line: {line}"
+            );
+        }
+    }
 }
@@ -0,0 +1,426 @@
+//! Read-only traversal of the panes in a parsed layout, for inspecting layouts without
+//! rewriting them.
+
+use crate::editor::rules_for;
+use crate::kdl::{TransformOptions, get_entry_string_value, is_pane_node, read_args};
+use crate::nvim::{EXECUTABLE_EXTENSIONS, is_nvim, nvim_file_args, program_stem};
+use kdl::{KdlDocument, KdlNode};
+
+/// A pane or pane template with a `command`, as yielded by [`PaneWalker`].
+#[derive(Debug, Clone)]
+pub struct PaneInfo<'a> {
+    /// The pane node itself.
+    pub node: &'a KdlNode,
+    /// The raw `command` attribute.
+    pub command: String,
+    /// The pane's args, from its args node or an inline `args="..."` property.
+    pub args: Vec<String>,
+    /// Basename of the recognized editor the command runs, if any.
+    pub editor: Option<String>,
+}
+
+/// Iterates, depth-first in document order, over every pane (or `pane_template`) with a
+/// `command` attribute, including panes nested in tabs, layouts and other panes.
+///
+/// ```
+/// use kdl::KdlDocument;
+/// use zellij_command_hook::walker::PaneWalker;
+///
+/// let doc: KdlDocument = r#"
+/// layout {
+///     pane command="/nix/store/abc-neovim/bin/nvim" { args "--cmd" "x=1" "a.txt"; }
+///     pane command="bacon"
+/// }
+/// "#
+/// .parse()
+/// .unwrap();
+///
+/// let editors: Vec<_> = PaneWalker::new(&doc).filter_editor_panes().collect();
+/// assert_eq!(editors.len(), 1);
+/// assert_eq!(editors[0].editor.as_deref(), Some("nvim"));
+/// assert_eq!(editors[0].args, ["--cmd", "x=1", "a.txt"]);
+/// ```
+pub struct PaneWalker<'a> {
+    stack: Vec<&'a KdlNode>,
+    options: TransformOptions,
+}
+
+impl<'a> PaneWalker<'a> {
+    /// Walks `document` using the default editors and args node name.
+    pub fn new(document: &'a KdlDocument) -> Self {
+        Self::with_options(document, TransformOptions::default())
+    }
+
+    /// Walks `document`, detecting `options.editors` and reading args from `options.args_node`.
+    pub fn with_options(document: &'a KdlDocument, options: TransformOptions) -> Self {
+        Self {
+            stack: document.nodes().iter().rev().collect(),
+            options,
+        }
+    }
+
+    /// Only yields panes running a recognized editor.
+    pub fn filter_editor_panes(self) -> impl Iterator<Item = PaneInfo<'a>> {
+        self.filter(|pane| pane.editor.is_some())
+    }
+
+    fn detect_editor(&self, command: &str) -> Option<String> {
+        if is_nvim(command, self.options.ignore_case)
+            && self.options.editors.iter().any(|e| e == "nvim")
+        {
+            return Some("nvim".to_string());
+        }
+        rules_for(&self.options.editors)
+            .into_iter()
+            .find(|r| r.matches(command, self.options.ignore_case))
+            .map(|r| r.name)
+    }
+}
+
+impl<'a> Iterator for PaneWalker<'a> {
+    type Item = PaneInfo<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if let Some(children) = node.children() {
+                self.stack.extend(children.nodes().iter().rev());
+            }
+            if !is_pane_node(node) {
+                continue;
+            }
+            let Some(command) = get_entry_string_value(node, "command") else {
+                continue;
+            };
+
+            return Some(PaneInfo {
+                node,
+                args: read_args(node, &self.options),
+                editor: self.detect_editor(&command),
+                command,
+            });
+        }
+        None
+    }
+}
+
+/// Two nvim panes in the same tab that open some of the same files, as found by
+/// [`redundant_panes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedundantPanes {
+    pub file_path: String,
+    /// Name of the tab, if it has one.
+    pub tab: Option<String>,
+    /// Positions of the two panes among the tab's nvim panes, counting from 1.
+    pub panes: (usize, usize),
+    /// Files both panes open.
+    pub shared_files: Vec<String>,
+}
+
+impl std::fmt::Display for RedundantPanes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.file_path)?;
+        match &self.tab {
+            Some(name) => write!(f, "tab {:?}", name)?,
+            None => write!(f, "layout")?,
+        }
+        write!(
+            f,
+            ": nvim panes {} and {} both open {}",
+            self.panes.0,
+            self.panes.1,
+            self.shared_files.join(" ")
+        )
+    }
+}
+
+/// Finds pairs of nvim panes within the same tab whose file args overlap. A document without
+/// tabs is treated as a single unnamed tab.
+pub fn redundant_panes(document: &KdlDocument, options: &TransformOptions) -> Vec<RedundantPanes> {
+    let mut tabs = Vec::new();
+    collect_tabs(document.nodes(), &mut tabs);
+
+    let mut groups: Vec<(Option<String>, &KdlDocument)> = tabs
+        .into_iter()
+        .filter_map(|tab| Some((get_entry_string_value(tab, "name"), tab.children()?)))
+        .collect();
+    if groups.is_empty() {
+        groups.push((None, document));
+    }
+
+    let mut redundant = Vec::new();
+    for (tab, children) in groups {
+        let files: Vec<Vec<String>> = PaneWalker::with_options(children, options.clone())
+            .filter(|pane| pane.editor.as_deref() == Some("nvim"))
+            .map(|pane| pane_files(&pane, options))
+            .collect();
+
+        for (i, first) in files.iter().enumerate() {
+            for (j, second) in files.iter().enumerate().skip(i + 1) {
+                let shared_files: Vec<String> = first
+                    .iter()
+                    .filter(|file| second.contains(file))
+                    .cloned()
+                    .collect();
+                if !shared_files.is_empty() {
+                    redundant.push(RedundantPanes {
+                        file_path: String::new(),
+                        tab: tab.clone(),
+                        panes: (i + 1, j + 1),
+                        shared_files,
+                    });
+                }
+            }
+        }
+    }
+    redundant
+}
+
+/// A pane that mentions a known editor but isn't simplified, as found by
+/// [`unrecognized_panes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrecognizedPane {
+    pub file_path: String,
+    /// The pane's command and args.
+    pub command: String,
+    /// Why the pane wasn't matched.
+    pub reason: String,
+}
+
+impl std::fmt::Display for UnrecognizedPane {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.file_path, self.command, self.reason)
+    }
+}
+
+/// Finds panes that aren't recognized as running an editor, but whose program name contains
+/// one of `options.editors`, or whose args run one (e.g. `bash -c nvim`).
+pub fn unrecognized_panes(
+    document: &KdlDocument,
+    options: &TransformOptions,
+) -> Vec<UnrecognizedPane> {
+    PaneWalker::with_options(document, options.clone())
+        .filter(|pane| pane.editor.is_none())
+        .filter_map(|pane| {
+            let program = program_stem(&pane.command, EXECUTABLE_EXTENSIONS, options.ignore_case);
+            let reason = options.editors.iter().find_map(|editor| {
+                if program.contains(editor.as_str()) {
+                    return Some(format!(
+                        "{} contains {} but isn't a recognized editor name",
+                        program, editor
+                    ));
+                }
+                let runs_editor =
+                    pane.args
+                        .iter()
+                        .flat_map(|arg| arg.split_whitespace())
+                        .any(|word| {
+                            program_stem(word, EXECUTABLE_EXTENSIONS, options.ignore_case) == editor
+                        });
+                runs_editor.then(|| {
+                    format!(
+                        "{} is run through {}, which isn't an editor",
+                        editor, program
+                    )
+                })
+            })?;
+            let mut command = pane.command.clone();
+            for arg in &pane.args {
+                command.push(' ');
+                command.push_str(arg);
+            }
+            Some(UnrecognizedPane {
+                file_path: String::new(),
+                command,
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Whether a layout has any `pane` node outside of templates and swap layouts. Layouts
+/// without one, like an empty or stub `session-layout.kdl`, have nothing to resurrect.
+pub fn has_panes(document: &KdlDocument) -> bool {
+    document.nodes().iter().any(|node| {
+        let name = node.name().value();
+        if name.ends_with("_template") || name.starts_with("swap_") {
+            return false;
+        }
+        name == "pane" || node.children().is_some_and(has_panes)
+    })
+}
+
+/// Collects the `tab` nodes among `nodes` and their descendants, without looking inside tabs.
+fn collect_tabs<'a>(nodes: &'a [KdlNode], tabs: &mut Vec<&'a KdlNode>) {
+    for node in nodes {
+        if node.name().value() == "tab" {
+            tabs.push(node);
+        } else if let Some(children) = node.children() {
+            collect_tabs(children.nodes(), tabs);
+        }
+    }
+}
+
+/// The files an nvim pane opens, without a leading `+` command.
+fn pane_files(pane: &PaneInfo, options: &TransformOptions) -> Vec<String> {
+    let mut tokens = vec![pane.command.as_str()];
+    tokens.extend(pane.args.iter().map(String::as_str));
+    nvim_file_args(&tokens, options.ignore_case)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|arg| !arg.starts_with('+'))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_walk_two_pane_layout() {
+        let doc: KdlDocument = r#"layout {
+    tab name="edit" {
+        pane split_direction="vertical" {
+            pane command="/home/zach/.nix-profile/bin/nvim" {
+                args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "file.txt"
+            }
+            pane command="/usr/bin/vim" {
+                args "-R" "hosts"
+            }
+            pane command="bacon"
+        }
+    }
+}"#
+        .parse()
+        .unwrap();
+
+        let panes: Vec<PaneInfo> = PaneWalker::new(&doc).filter_editor_panes().collect();
+        let summary: Vec<(&str, Option<&str>, Vec<String>)> = panes
+            .iter()
+            .map(|p| (p.command.as_str(), p.editor.as_deref(), p.args.clone()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "/home/zach/.nix-profile/bin/nvim",
+                    Some("nvim"),
+                    vec![
+                        "--cmd".to_string(),
+                        "lua vim.opt.packpath:prepend('/nix/store/test')".to_string(),
+                        "file.txt".to_string()
+                    ]
+                ),
+                (
+                    "/usr/bin/vim",
+                    Some("vim"),
+                    vec!["-R".to_string(), "hosts".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(PaneWalker::new(&doc).count(), 3);
+    }
+
+    #[test]
+    fn test_redundant_panes_in_same_tab() {
+        let doc: KdlDocument = r#"layout {
+    tab name="edit" {
+        pane command="/home/zach/.nix-profile/bin/nvim" {
+            args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "notes.md" "a.txt"
+        }
+        pane command="nvim" {
+            args "+42" "a.txt"
+        }
+        pane command="nvim" {
+            args "b.txt"
+        }
+    }
+    tab name="other" {
+        pane command="nvim" {
+            args "a.txt"
+        }
+    }
+}"#
+        .parse()
+        .unwrap();
+
+        let redundant = redundant_panes(&doc, &TransformOptions::default());
+
+        assert_eq!(
+            redundant,
+            vec![RedundantPanes {
+                file_path: String::new(),
+                tab: Some("edit".to_string()),
+                panes: (1, 2),
+                shared_files: vec!["a.txt".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_has_panes() {
+        let cases = [
+            ("", false),
+            ("layout {\n}\n", false),
+            (
+                "layout {\n    default_tab_template {\n        pane size=1 borderless=true {\n            plugin location=\"zellij:tab-bar\"\n        }\n        children\n    }\n    tab name=\"Tab #1\"\n}\n",
+                false,
+            ),
+            (
+                "layout {\n    tab name=\"edit\" {\n        pane\n    }\n}\n",
+                true,
+            ),
+            ("pane command=\"htop\"\n", true),
+        ];
+
+        for (input, expected) in cases {
+            dbg!(input);
+            let doc: KdlDocument = input.parse().unwrap();
+            assert_eq!(has_panes(&doc), expected, "Failed on input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_unrecognized_panes() {
+        let doc: KdlDocument = r#"layout {
+    tab name="edit" {
+        pane command="/usr/bin/nvimdiff" {
+            args "a.txt" "b.txt"
+        }
+        pane command="bash" {
+            args "-c" "nvim"
+        }
+        pane command="/usr/bin/nvim" {
+            args "a.txt"
+        }
+        pane command="bash" {
+            args "init.vim"
+        }
+    }
+}"#
+        .parse()
+        .unwrap();
+
+        let found = unrecognized_panes(&doc, &TransformOptions::default());
+
+        let summary: Vec<(&str, &str)> = found
+            .iter()
+            .map(|p| (p.command.as_str(), p.reason.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "/usr/bin/nvimdiff a.txt b.txt",
+                    "nvimdiff contains nvim but isn't a recognized editor name"
+                ),
+                (
+                    "bash -c nvim",
+                    "nvim is run through bash, which isn't an editor"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Minimal zip archive support, enough to audit archived zellij caches without pulling in a
+//! zip crate: reading stored and deflated entries, and writing archives back uncompressed.
+//! Zip64, encryption and multi-disk archives are not supported.
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+
+/// General purpose flag: the entry is encrypted.
+const FLAG_ENCRYPTED: u16 = 0x0001;
+/// General purpose flag: the entry name is UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// A file (or directory, with a name ending in `/`) in an archive, decompressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+    /// Modification time and date in MS-DOS format, carried over on rewrite.
+    pub dos_time: u16,
+    pub dos_date: u16,
+}
+
+/// Reads every entry of a zip archive, in central directory order.
+pub fn read_archive(bytes: &[u8]) -> Result<Vec<ZipEntry>, String> {
+    let eocd = find_end_of_central_dir(bytes)?;
+    let count = read_u16(bytes, eocd + 10)? as usize;
+    let mut offset = read_u32(bytes, eocd + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if read_u32(bytes, offset)? != CENTRAL_HEADER_SIG {
+            return Err("corrupt central directory".to_string());
+        }
+        let flags = read_u16(bytes, offset + 8)?;
+        let method = read_u16(bytes, offset + 10)?;
+        let dos_time = read_u16(bytes, offset + 12)?;
+        let dos_date = read_u16(bytes, offset + 14)?;
+        let crc = read_u32(bytes, offset + 16)?;
+        let compressed_size = read_u32(bytes, offset + 20)?;
+        let size = read_u32(bytes, offset + 24)?;
+        let name_len = read_u16(bytes, offset + 28)? as usize;
+        let extra_len = read_u16(bytes, offset + 30)? as usize;
+        let comment_len = read_u16(bytes, offset + 32)? as usize;
+        let local_offset = read_u32(bytes, offset + 42)? as usize;
+        let name_bytes = slice(bytes, offset + 46, name_len)?;
+        let name = String::from_utf8_lossy(name_bytes).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if flags & FLAG_ENCRYPTED != 0 {
+            return Err(format!("{}: encrypted entries are not supported", name));
+        }
+        if [compressed_size, size, local_offset as u32].contains(&u32::MAX) {
+            return Err(format!("{}: zip64 entries are not supported", name));
+        }
+
+        if read_u32(bytes, local_offset)? != LOCAL_HEADER_SIG {
+            return Err(format!("{}: corrupt local header", name));
+        }
+        let data_start = local_offset
+            + 30
+            + read_u16(bytes, local_offset + 26)? as usize
+            + read_u16(bytes, local_offset + 28)? as usize;
+        let raw = slice(bytes, data_start, compressed_size as usize)?;
+        let data = match method {
+            METHOD_STORED => raw.to_vec(),
+            METHOD_DEFLATED => inflate(raw).map_err(|e| format!("{}: {}", name, e))?,
+            other => {
+                return Err(format!(
+                    "{}: compression method {} is not supported",
+                    name, other
+                ));
+            }
+        };
+        if data.len() != size as usize || crc32(&data) != crc {
+            return Err(format!("{}: checksum mismatch", name));
+        }
+
+        entries.push(ZipEntry {
+            name,
+            data,
+            dos_time,
+            dos_date,
+        });
+    }
+    Ok(entries)
+}
+
+/// Writes `entries` as a zip archive. Entries are stored uncompressed.
+pub fn write_archive(entries: &[ZipEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+
+    for entry in entries {
+        let local_offset = out.len() as u32;
+        let crc = crc32(&entry.data);
+        let size = entry.data.len() as u32;
+        let name = entry.name.as_bytes();
+
+        // Fields shared by the local and central headers, from "version needed" on
+        let mut common = Vec::new();
+        push_u16(&mut common, 20);
+        push_u16(&mut common, FLAG_UTF8);
+        push_u16(&mut common, METHOD_STORED);
+        push_u16(&mut common, entry.dos_time);
+        push_u16(&mut common, entry.dos_date);
+        push_u32(&mut common, crc);
+        push_u32(&mut common, size);
+        push_u32(&mut common, size);
+        push_u16(&mut common, name.len() as u16);
+        push_u16(&mut common, 0); // extra field length
+
+        push_u32(&mut out, LOCAL_HEADER_SIG);
+        out.extend_from_slice(&common);
+        out.extend_from_slice(name);
+        out.extend_from_slice(&entry.data);
+
+        push_u32(&mut central, CENTRAL_HEADER_SIG);
+        push_u16(&mut central, 20); // version made by
+        central.extend_from_slice(&common);
+        push_u16(&mut central, 0); // comment length
+        push_u16(&mut central, 0); // disk number
+        push_u16(&mut central, 0); // internal attributes
+        push_u32(&mut central, 0); // external attributes
+        push_u32(&mut central, local_offset);
+        central.extend_from_slice(name);
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    push_u32(&mut out, END_OF_CENTRAL_DIR_SIG);
+    push_u16(&mut out, 0); // this disk
+    push_u16(&mut out, 0); // disk with the central directory
+    push_u16(&mut out, entries.len() as u16);
+    push_u16(&mut out, entries.len() as u16);
+    push_u32(&mut out, central.len() as u32);
+    push_u32(&mut out, central_offset);
+    push_u16(&mut out, 0); // comment length
+    out
+}
+
+/// Finds the end of central directory record, searching back over a possible archive comment.
+fn find_end_of_central_dir(bytes: &[u8]) -> Result<usize, String> {
+    let min_start = bytes.len().saturating_sub(22 + u16::MAX as usize);
+    (min_start..=bytes.len().saturating_sub(22))
+        .rev()
+        .find(|&pos| read_u32(bytes, pos) == Ok(END_OF_CENTRAL_DIR_SIG))
+        .ok_or_else(|| "not a zip archive".to_string())
+}
+
+fn slice(bytes: &[u8], start: usize, len: usize) -> Result<&[u8], String> {
+    start
+        .checked_add(len)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or_else(|| "unexpected end of archive".to_string())
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> Result<u16, String> {
+    let b = slice(bytes, pos, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> Result<u32, String> {
+    let b = slice(bytes, pos, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// CRC-32 as used by zip (IEEE polynomial, reflected).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads a deflate stream least significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| "truncated deflate stream".to_string())?;
+        let value = (byte >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(value as u32)
+    }
+
+    fn bits(&mut self, count: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for idx in 0..count {
+            value |= self.bit()? << idx;
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code: how many codes each length has, and the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= reader.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+/// Decompresses a raw deflate stream (RFC 1951).
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bit()? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = slice(data, reader.pos, 4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err("corrupt stored block".to_string());
+                }
+                out.extend_from_slice(slice(data, reader.pos + 4, len as usize)?);
+                reader.pos += 4 + len as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Reads the literal/length and distance codes from a dynamic block header.
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &idx in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[idx] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| "repeat with no previous length".to_string())?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err("code lengths overrun".to_string());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+/// Decodes one compressed block's symbols into `out`, up to the end-of-block symbol.
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let idx = symbol - 257;
+                if idx >= LENGTH_BASE.len() {
+                    return Err("invalid length symbol".to_string());
+                }
+                let len = LENGTH_BASE[idx] as usize + reader.bits(LENGTH_EXTRA[idx])? as usize;
+
+                let idx = distances.decode(reader)? as usize;
+                if idx >= DIST_BASE.len() {
+                    return Err("invalid distance symbol".to_string());
+                }
+                let dist = DIST_BASE[idx] as usize + reader.bits(DIST_EXTRA[idx])? as usize;
+                if dist > out.len() {
+                    return Err("distance too far back".to_string());
+                }
+                let start = out.len() - dist;
+                for offset in 0..len {
+                    out.push(out[start + offset]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read_deflated_archive() {
+        // Built with `zip -9`, so the layout is stored with dynamic Huffman codes
+        let bytes = std::fs::read("tests/fixtures/sessions.zip").unwrap();
+        let expected = std::fs::read("tests/fixtures/mediactl_input/session-layout.kdl").unwrap();
+
+        let entries = read_archive(&bytes).unwrap();
+
+        let layout = entries
+            .iter()
+            .find(|e| e.name.ends_with("session-layout.kdl"))
+            .unwrap();
+        assert_eq!(layout.data, expected);
+    }
+
+    #[test]
+    fn test_write_archive_roundtrip() {
+        let entries = vec![
+            ZipEntry {
+                name: "session_info/".to_string(),
+                data: Vec::new(),
+                dos_time: 0,
+                dos_date: 0x21,
+            },
+            ZipEntry {
+                name: "session_info/a/session-layout.kdl".to_string(),
+                data: b"layout {\n}\n".to_vec(),
+                dos_time: 0x6000,
+                dos_date: 0x5a21,
+            },
+        ];
+
+        let bytes = write_archive(&entries);
+
+        assert_eq!(read_archive(&bytes).unwrap(), entries);
+        assert!(read_archive(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn test_inflate_fixed_and_stored_blocks() {
+        // zlib.compressobj(wbits=-15) output of b"nvim nvim nvim a.txt" (fixed Huffman codes)
+        let fixed = [
+            0xcb, 0x2b, 0xcb, 0xcc, 0x55, 0xc8, 0x83, 0x13, 0x89, 0x7a, 0x25, 0x15, 0x25, 0x00,
+        ];
+        assert_eq!(inflate(&fixed).unwrap(), b"nvim nvim nvim a.txt");
+
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(inflate(&stored).unwrap(), b"abc");
+    }
+}
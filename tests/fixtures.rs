@@ -0,0 +1,59 @@
+use pretty_assertions::assert_eq;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zellij_command_hook::kdl::process_kdl_content;
+
+const FIXTURES_DIR: &str = "tests/fixtures";
+
+/// Finds every `<name>_input/<file>` fixture with a matching `<name>_expected/<file>`.
+fn fixture_pairs() -> Vec<(PathBuf, PathBuf)> {
+    let mut pairs = Vec::new();
+    for entry in fs::read_dir(FIXTURES_DIR).unwrap().flatten() {
+        let input_dir = entry.path();
+        let Some(name) = input_dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix("_input"))
+        else {
+            continue;
+        };
+        let expected_dir = Path::new(FIXTURES_DIR).join(format!("{name}_expected"));
+
+        for file in fs::read_dir(&input_dir).unwrap().flatten() {
+            let expected = expected_dir.join(file.file_name());
+            assert!(
+                expected.is_file(),
+                "Missing expected fixture: {}",
+                expected.display()
+            );
+            pairs.push((file.path(), expected));
+        }
+    }
+    pairs.sort();
+    pairs
+}
+
+#[test]
+fn test_fixtures_match_expected() {
+    let pairs = fixture_pairs();
+    assert!(
+        pairs
+            .iter()
+            .any(|(input, _)| input.starts_with("tests/fixtures/mediactl_input")),
+        "mediactl fixture not found"
+    );
+
+    for (input_path, expected_path) in pairs {
+        dbg!(&input_path);
+        let input = fs::read_to_string(&input_path).unwrap();
+        let expected = fs::read_to_string(&expected_path).unwrap();
+
+        let (result, _) = process_kdl_content(&input);
+        assert_eq!(
+            result,
+            expected,
+            "Mismatch for fixture: {}",
+            input_path.display()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use pretty_assertions::assert_eq;
 use std::fs;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
 use tempfile::tempdir;
 
 // TODO: test ignored because nix builds failing
@@ -33,6 +35,8 @@ fn test_scan_layouts_integration() {
             "--quiet",
             "--",
             "scan-layouts",
+            "--log-file",
+            temp.path().join("run.log").to_str().unwrap(),
             "--quiet",
             temp.path().to_str().unwrap(),
         ])
@@ -52,3 +56,616 @@ fn test_scan_layouts_integration() {
 
     assert_eq!(result.trim(), expected.trim());
 }
+
+#[test]
+fn test_stdin_to_stdout() {
+    let input = fs::read_to_string("tests/fixtures/mediactl_input/session-layout.kdl").unwrap();
+    let expected =
+        fs::read_to_string("tests/fixtures/mediactl_expected/session-layout.kdl").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args(["scan-layouts", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(
+        output.status.success(),
+        "Command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+#[test]
+fn test_verify() {
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "verify",
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+            "tests/fixtures/mediactl_expected/session-layout.kdl",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "verify",
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("--- a/"), "{stdout}");
+}
+
+#[test]
+fn test_verify_uses_config() {
+    let config_home = tempdir().unwrap();
+    let layout = config_home.path().join("session-layout.kdl");
+    fs::write(
+        &layout,
+        "pane command=\"/usr/bin/nvim\" {\n    args \"--cmd\" \"set nu\" \"a.txt\"\n}\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args(["verify", layout.to_str().unwrap(), layout.to_str().unwrap()])
+        .env("ZCH_EDITORS", "vim")
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "{output:?}");
+
+    let broken = config_home.path().join("broken.kdl");
+    fs::write(&broken, "pane {").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args(["verify", broken.to_str().unwrap(), broken.to_str().unwrap()])
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error: parsing"), "{stderr}");
+}
+
+#[test]
+fn test_stdin_dry_run_prints_diff() {
+    let input = "layout {\n    pane command=\"/nix/store/abc/bin/nvim\" {\n        args \"--cmd\" \"lua vim.g.x=1\" \"a.txt\"\n    }\n}\n";
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args(["scan-layouts", "--stdin", "--dry-run"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "\
+--- a/stdin
++++ b/stdin
+@@ -1,5 +1,5 @@
+ layout {
+-    pane command=\"/nix/store/abc/bin/nvim\" {
+-        args \"--cmd\" \"lua vim.g.x=1\" \"a.txt\"
++    pane command=\"nvim\" {
++        args \"a.txt\"
+     }
+ }
+"
+    );
+}
+
+#[test]
+fn test_extended_exit_codes() {
+    let temp = tempdir().unwrap();
+    let session_dir = temp.path().join("session_info/my_session");
+    fs::create_dir_all(&session_dir).unwrap();
+    fs::copy(
+        "tests/fixtures/mediactl_input/session-layout.kdl",
+        session_dir.join("session-layout.kdl"),
+    )
+    .unwrap();
+
+    let run = || {
+        Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+            .args([
+                "scan-layouts",
+                "--log-file",
+                temp.path().join("run.log").to_str().unwrap(),
+                "--quiet",
+                "--exit-codes",
+                "extended",
+                temp.path().to_str().unwrap(),
+            ])
+            .output()
+            .expect("Failed to run command")
+    };
+
+    // First run rewrites the layout
+    assert_eq!(run().status.code(), Some(10));
+    // Second run finds nothing left to simplify
+    assert_eq!(run().status.code(), Some(0));
+}
+
+#[test]
+fn test_dry_run_detect_exit_code() {
+    let temp = tempdir().unwrap();
+    let layout = temp.path().join("session-layout.kdl");
+    let run = || {
+        Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+            .args([
+                "scan-layouts",
+                "--dry-run",
+                "--detect",
+                temp.path().to_str().unwrap(),
+            ])
+            .output()
+            .expect("Failed to run command")
+    };
+
+    fs::copy("tests/fixtures/mediactl_input/session-layout.kdl", &layout).unwrap();
+    let output = run();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("DRY RUN"));
+    assert_eq!(
+        fs::read_to_string(&layout).unwrap(),
+        fs::read_to_string("tests/fixtures/mediactl_input/session-layout.kdl").unwrap()
+    );
+
+    fs::copy(
+        "tests/fixtures/mediactl_expected/session-layout.kdl",
+        &layout,
+    )
+    .unwrap();
+    assert_eq!(run().status.code(), Some(0));
+}
+
+#[test]
+fn test_editors_from_env() {
+    let input = r#"pane command="/usr/bin/vim" {
+    args "-u" "/nix/store/vimrc" "hosts"
+}
+pane command="/usr/bin/emacs" {
+    args "--eval" "(server-start)" "todo.org"
+}"#;
+    let expected = r#"pane command="vim" {
+    args "hosts"
+}
+pane command="/usr/bin/emacs" {
+    args "--eval" "(server-start)" "todo.org"
+}"#;
+    let config_home = tempdir().unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args(["scan-layouts", "--stdin", "--simplify-other-editors"])
+        .env("ZCH_EDITORS", "nvim,vim")
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(
+        output.status.success(),
+        "Command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+#[test]
+fn test_only_changed_prints_modified_paths() {
+    let temp = tempdir().unwrap();
+    for session in ["dirty", "clean"] {
+        fs::create_dir_all(temp.path().join(session)).unwrap();
+    }
+    let dirty = temp.path().join("dirty/session-layout.kdl");
+    fs::copy("tests/fixtures/mediactl_input/session-layout.kdl", &dirty).unwrap();
+    fs::copy(
+        "tests/fixtures/mediactl_expected/session-layout.kdl",
+        temp.path().join("clean/session-layout.kdl"),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "scan-layouts",
+            "--log-file",
+            temp.path().join("run.log").to_str().unwrap(),
+            "--only-changed",
+            temp.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\n", dirty.display())
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_editor_exists() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let input = r#"pane command="/nix/store/abc/bin/nvim" {
+    args "--cmd" "lua vim.opt.packpath:prepend('/nix/store/test')" "a.txt"
+}"#;
+    let with_nvim = tempdir().unwrap();
+    let fake_nvim = with_nvim.path().join("nvim");
+    fs::write(&fake_nvim, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&fake_nvim, fs::Permissions::from_mode(0o755)).unwrap();
+    let without_nvim = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+
+    let run = |path: &std::path::Path, input: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+            .args(["scan-layouts", "--stdin", "--verify-editor-exists"])
+            .env("PATH", path)
+            .env("XDG_CONFIG_HOME", config_home.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn command");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = run(with_nvim.path(), input);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "pane command=\"nvim\" {\n    args \"a.txt\"\n}"
+    );
+
+    let output = run(without_nvim.path(), input);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "pane command=\"/nix/store/abc/bin/nvim\" {\n    args \"a.txt\"\n}"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nvim is not on PATH"));
+
+    let dr_tab = format!("tab name=\"dr x\" {{\n{}\n}}", input);
+    let output = run(without_nvim.path(), &dr_tab);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "tab name=\"dr x\" {\npane command=\"direnv\" {\n    args \"exec\" \".\" \"/nix/store/abc/bin/nvim\" \"a.txt\"\n}\n}"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nvim is not on PATH"));
+}
+
+#[test]
+fn test_profile_prints_phases() {
+    let temp = tempdir().unwrap();
+    fs::copy(
+        "tests/fixtures/mediactl_input/session-layout.kdl",
+        temp.path().join("session-layout.kdl"),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "scan-layouts",
+            "--dry-run",
+            "--profile",
+            temp.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for phase in ["discover", "read", "transform", "write"] {
+        assert!(stderr.contains(phase), "Missing {phase} in: {stderr}");
+    }
+}
+
+#[test]
+fn test_log_file_creates_parent_dirs() {
+    let temp = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+    let log_file = temp.path().join("logs/zch/run.log");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .arg("--log-file")
+        .arg(&log_file)
+        .env(
+            "RESURRECT_COMMAND",
+            "/nix/store/abc/bin/nvim --cmd x=1 a.txt",
+        )
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "nvim a.txt\n");
+    assert!(log_file.parent().unwrap().is_dir());
+    let log = fs::read_to_string(&log_file).unwrap();
+    assert!(log.contains(r#""formatted_command":"nvim a.txt""#), "{log}");
+}
+
+#[test]
+fn test_print_command_only_skips_log() {
+    let temp = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+    let log_file = temp.path().join("run.log");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .arg("--print-command-only")
+        .arg("--log-file")
+        .arg(&log_file)
+        .env(
+            "RESURRECT_COMMAND",
+            "/nix/store/abc/bin/nvim --cmd x=1 a.txt",
+        )
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "nvim a.txt\n");
+    assert!(!log_file.exists());
+}
+
+#[test]
+fn test_hook_skips_flag_values() {
+    let config_home = tempdir().unwrap();
+    let cases = [
+        ("nvim --cmd x file.txt", "nvim file.txt\n"),
+        (
+            "/nix/store/abc/bin/nvim -u init.lua notes.md",
+            "nvim notes.md\n",
+        ),
+        ("nvim -c split a.txt b.txt", "nvim a.txt b.txt\n"),
+        ("nvim -S notes.md", "nvim notes.md\n"),
+    ];
+
+    for (command, expected) in cases {
+        dbg!(command);
+        let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+            .arg("--print-command-only")
+            .env("RESURRECT_COMMAND", command)
+            .env("XDG_CONFIG_HOME", config_home.path())
+            .output()
+            .expect("Failed to run command");
+
+        assert!(output.status.success(), "Failed on input: {command}");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            expected,
+            "Failed on input: {command}"
+        );
+    }
+}
+
+#[test]
+fn test_session_allowlist() {
+    let cache = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+    let layout = |name: &str| {
+        cache
+            .path()
+            .join("zellij/0.40.1/session_info")
+            .join(name)
+            .join("session-layout.kdl")
+    };
+    for name in ["a", "b", "c"] {
+        fs::create_dir_all(layout(name).parent().unwrap()).unwrap();
+        fs::copy(
+            "tests/fixtures/mediactl_input/session-layout.kdl",
+            layout(name),
+        )
+        .unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "scan-layouts",
+            "--log-file",
+            config_home.path().join("run.log").to_str().unwrap(),
+            "--only-changed",
+            "--session",
+            "a",
+            "--session",
+            "c",
+        ])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success(), "{output:?}");
+    let changed: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            layout("a").display().to_string(),
+            layout("c").display().to_string()
+        ]
+    );
+    let original = fs::read_to_string("tests/fixtures/mediactl_input/session-layout.kdl").unwrap();
+    assert_eq!(fs::read_to_string(layout("b")).unwrap(), original);
+}
+
+#[test]
+fn test_session_only_newest_layout() {
+    let cache = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+    let zellij = cache.path().join("zellij");
+    let copies = [
+        "0.40.1/session_info/foo",
+        "0.41.2/session_info/foo",
+        "0.41.2/session_info/bar",
+        "backups/foo",
+    ];
+    for dir in copies {
+        let layout = zellij.join(dir).join("session-layout.kdl");
+        fs::create_dir_all(layout.parent().unwrap()).unwrap();
+        fs::copy("tests/fixtures/mediactl_input/session-layout.kdl", &layout).unwrap();
+        if !dir.starts_with("0.41.2") {
+            let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+            fs::File::options()
+                .write(true)
+                .open(&layout)
+                .unwrap()
+                .set_modified(an_hour_ago)
+                .unwrap();
+        }
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "scan-layouts",
+            "--log-file",
+            config_home.path().join("run.log").to_str().unwrap(),
+            "--only-changed",
+            "--session",
+            "foo",
+            "--session",
+            "bar",
+        ])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success(), "{output:?}");
+    let layout = |dir: &str| zellij.join(dir).join("session-layout.kdl");
+    let changed: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            layout("0.41.2/session_info/bar").display().to_string(),
+            layout("0.41.2/session_info/foo").display().to_string()
+        ]
+    );
+    let original = fs::read_to_string("tests/fixtures/mediactl_input/session-layout.kdl").unwrap();
+    for dir in ["0.40.1/session_info/foo", "backups/foo"] {
+        assert_eq!(fs::read_to_string(layout(dir)).unwrap(), original);
+    }
+}
+
+#[test]
+fn test_editor_alias_in_summary() {
+    let temp = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+    let layout = temp.path().join("session-layout.kdl");
+    fs::copy("tests/fixtures/editors_input/session-layout.kdl", &layout).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "scan-layouts",
+            "--log-file",
+            temp.path().join("run.log").to_str().unwrap(),
+            "--editor-alias",
+            "nvim=Neovim",
+            "--simplify-other-editors",
+            temp.path().to_str().unwrap(),
+        ])
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("By editor: Neovim: 2 panes, vim: 1 pane"),
+        "{stdout}"
+    );
+    let rewritten = fs::read_to_string(&layout).unwrap();
+    assert!(rewritten.contains("command=\"nvim\""));
+    assert!(!rewritten.contains("Neovim"));
+}
+
+#[test]
+fn test_json_format_warnings_on_stderr() {
+    let temp = tempdir().unwrap();
+    let empty_path = tempdir().unwrap();
+    let config_home = tempdir().unwrap();
+    fs::write(
+        temp.path().join("session-layout.kdl"),
+        "layout {\n    pane command=\"/nix/store/abc/bin/nvim\" {\n        args \"--cmd\" \"x=1\" \"a.txt\"\n    }\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zellij-command-hook"))
+        .args([
+            "scan-layouts",
+            "--dry-run",
+            "--format",
+            "json",
+            "--verify-editor-exists",
+            temp.path().to_str().unwrap(),
+        ])
+        .env("PATH", empty_path.path())
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("Failed to run command");
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert!(!lines.is_empty());
+    for line in lines {
+        assert!(
+            line.starts_with(r#"{"level":"warn","message":""#),
+            "Failed on line: {line}"
+        );
+        assert!(line.ends_with('}'), "Failed on line: {line}");
+        assert!(
+            line.contains("nvim is not on PATH"),
+            "Failed on line: {line}"
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.trim_end().starts_with('{') && stdout.trim_end().ends_with('}'));
+}